@@ -1,206 +1,813 @@
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tokio::sync::Notify;
+mod activity;
+mod cache;
+mod custom;
+mod emulator;
+mod engine;
+mod history;
+mod hysteresis;
+mod index;
+mod libraries;
+mod matcher;
+mod pacing;
+mod privacy;
+mod power;
+mod proc_events;
+mod process;
+mod scanner;
+mod settings;
+mod source;
+mod status;
+mod steam;
+mod storage;
+#[cfg(test)]
+mod test_support;
+mod vdf;
 
-use serde::{Deserialize, Serialize};
-use sysinfo::{System, SystemExt, ProcessExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Deserializer, Serialize};
 use tauri::{AppHandle, Manager};
 
+use activity::{ActivitySet, RunningGame};
+use cache::{FetchOutcome, GamesCache};
+use hysteresis::Hysteresis;
+use pacing::ScanInterval;
+use process::SysinfoSource;
+use scanner::{Host, Scanner, ScannerPaths};
+pub use scanner::{ScannerInbox, ScannerState};
+pub use settings::ScannerSettings;
+use source::{SourceError, SourceLocation};
+use status::{ErrorKind, ScannerError};
+
+pub use activity::{LauncherPolicy, PrimaryPolicy};
+pub use history::{DayTotal, GameTotal, Session};
+pub use pacing::ScanMetrics;
+pub use privacy::IgnoreList;
+pub use source::GamesSource;
+pub use status::ScannerStatus;
+
 /// A single executable entry from the detectable games list.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct GameExecutable {
     pub os: String,
     pub name: String,
+    /// The executable is a launcher or store client rather than the game itself.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub is_launcher: bool,
+    /// Arguments the process must have been started with, e.g. `-game csgo`
+    /// for games sharing one engine executable.
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+/// A store listing of a detectable game, e.g. its Steam app id.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ThirdPartySku {
+    pub distributor: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub sku: Option<String>,
 }
 
 /// A detectable game entry sourced from Discord's API.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct DetectableGame {
     pub id: String,
     pub name: String,
     pub executables: Option<Vec<GameExecutable>>,
+    /// Genres from the detectable list, used as ignore list categories.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub themes: Vec<String>,
+    /// Other names the game is known by.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub aliases: Vec<String>,
+    /// Hash of the game's icon on Discord's CDN.
+    #[serde(default)]
+    pub icon_hash: Option<String>,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub third_party_skus: Vec<ThirdPartySku>,
+}
+
+/// Reads an explicit `null` as the field's default, as the upstream list
+/// sometimes sends `null` for empty lists instead of leaving them out.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
 }
 
 /// Payload emitted to the frontend when activity changes.
 #[derive(Clone, Debug, Serialize)]
 pub struct GameActivity {
+    /// The detectable application id, e.g. Discord's application id.
+    pub id: String,
     pub name: String,
     pub executable_name: Option<String>,
     pub is_running: bool,
+    /// Unix timestamp (seconds) the game started at.
+    pub started_at: u64,
+    pub aliases: Vec<String>,
+    pub icon_hash: Option<String>,
+    /// URL of the game's icon, if the detectable list has one.
+    pub icon_url: Option<String>,
+    /// Only a launcher of the game is running, not the game itself.
+    pub is_launcher: bool,
+    /// The emulator running the game, when `name` is the title of its content.
+    pub emulator: Option<String>,
+}
+
+impl GameActivity {
+    fn started(game: &RunningGame) -> GameActivity {
+        GameActivity {
+            executable_name: game.executable_name.clone(),
+            is_running: true,
+            ..GameActivity::stopped(game)
+        }
+    }
+
+    fn stopped(game: &RunningGame) -> GameActivity {
+        GameActivity {
+            id: game.id.clone(),
+            name: game.name.clone(),
+            executable_name: None,
+            is_running: false,
+            started_at: game.started_at,
+            aliases: game.aliases.clone(),
+            icon_hash: game.icon_hash.clone(),
+            icon_url: game
+                .icon_hash
+                .as_ref()
+                .map(|hash| format!("https://cdn.discordapp.com/app-icons/{}/{}.png", game.id, hash)),
+            is_launcher: game.is_launcher,
+            emulator: game.emulator.clone(),
+        }
+    }
+}
+
+/// Payload of the `games-list-updated` event, emitted when a new detectable
+/// games list replaces the watch list.
+#[derive(Clone, Debug, Serialize)]
+pub struct GamesListUpdate {
+    pub count: usize,
+    /// The source the list was loaded from, as shown by [`GamesSource`]'s `Display`.
+    pub source: String,
+}
+
+/// Payload describing every running game, emitted whenever the set changes.
+#[derive(Clone, Debug, Serialize)]
+pub struct GameActivitySet {
+    /// The game chosen by the primary policy, also reported via `game-activity`.
+    pub primary: Option<GameActivity>,
+    pub games: Vec<GameActivity>,
+}
+
+impl GameActivitySet {
+    fn new(activities: &ActivitySet, primary: Option<&RunningGame>) -> GameActivitySet {
+        GameActivitySet {
+            primary: primary.map(GameActivity::started),
+            games: activities.visible_games().map(GameActivity::started).collect(),
+        }
+    }
+}
+
+
+#[tauri::command]
+pub fn set_scanner_enabled(state: tauri::State<'_, ScannerState>, enabled: bool) -> Result<(), String> {
+    state.set_enabled(enabled)
+}
+
+/// Ends all activity and reinitializes the scanner from its settings, custom
+/// games and cached games list, as at launch. If the scanner task has exited,
+/// a new one is started in its place.
+#[tauri::command]
+pub async fn restart_game_scanner(app: AppHandle, state: tauri::State<'_, ScannerState>) -> Result<(), String> {
+    if !state.is_finished() && state.configure(Scanner::restart).await.is_ok() {
+        return Ok(());
+    }
+    log::info!("Scanner task has exited, starting a new one");
+    let settings = settings::settings_path(&app)
+        .map(|path| ScannerSettings::load(&path))
+        .unwrap_or_default();
+    let inbox = state.reopen(settings);
+    start(app, state.inner().clone(), inbox);
+    Ok(())
+}
+
+/// What the scanner is doing, or the error keeping it from working properly.
+#[tauri::command]
+pub async fn get_scanner_status(state: tauri::State<'_, ScannerState>) -> Result<ScannerStatus, String> {
+    state.query(|scanner| scanner.status.status()).await
+}
+
+fn list_error(e: &SourceError, retry_at: Option<u64>) -> ScannerError {
+    ScannerError {
+        kind: e.kind(),
+        message: e.to_string(),
+        retry_at,
+    }
+}
+
+#[tauri::command]
+pub async fn get_games_source(state: tauri::State<'_, ScannerState>) -> Result<GamesSource, String> {
+    state.query(|scanner| scanner.settings.games_source.clone()).await
+}
+
+/// Switches the detectable games source. The new source is loaded and validated
+/// before it is saved, so a bad URL or malformed file is reported to the caller
+/// and the current watch list stays in place. Returns the number of games loaded.
+#[tauri::command]
+pub async fn set_games_source(
+    app: AppHandle,
+    state: tauri::State<'_, ScannerState>,
+    source: GamesSource,
+) -> Result<usize, String> {
+    let fresh = match load_games(&app, &state.http, &source, None).await {
+        Ok(FetchOutcome::Modified(fresh)) => fresh,
+        Ok(FetchOutcome::NotModified) => return Err("source returned no games list".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    state
+        .configure(move |scanner| {
+            scanner.update_settings(|settings| settings.games_source = source.clone())?;
+            log::info!("Games source set to: {}", source);
+
+            let count = fresh.games.len();
+            scanner.apply_games_list(fresh);
+            scanner.update_status(|status| status.list_error = None);
+            Ok(count)
+        })
+        .await?
+}
+
+/// Refreshes the detectable games list now, sending the cached validators so
+/// an unchanged list isn't downloaded again. Returns the size of the current list.
+#[tauri::command]
+pub async fn refresh_games_list(
+    app: AppHandle,
+    state: tauri::State<'_, ScannerState>,
+) -> Result<GamesListUpdate, String> {
+    let source = state.query(|scanner| scanner.settings.games_source.clone()).await?;
+    let cached = cached_games(cache_path(&app).as_deref(), &source);
+    state.update(|scanner| scanner.update_status(|status| status.fetching_list = true))?;
+    let result = load_games(&app, &state.http, &source, cached.as_ref()).await;
+    let error = result.as_ref().err().map(|e| list_error(e, None));
+    state.update(|scanner| {
+        scanner.update_status(|status| {
+            status.fetching_list = false;
+            status.list_error = error;
+        })
+    })?;
+    let fresh = match result {
+        Ok(FetchOutcome::Modified(fresh)) => Some(fresh),
+        Ok(FetchOutcome::NotModified) => {
+            log::info!("Cached games list is up to date");
+            None
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    state
+        .configure(move |scanner| {
+            if let Some(fresh) = fresh {
+                // The source may have been switched while this request was in flight.
+                if scanner.settings.games_source != source {
+                    return Err("games source changed during the refresh".to_string());
+                }
+                scanner.apply_games_list(fresh);
+            }
+            Ok(GamesListUpdate {
+                count: scanner.fetched_games.len(),
+                source: source.to_string(),
+            })
+        })
+        .await?
+}
+
+/// Sets how often the detectable games list is refreshed in the background.
+/// With 0 hours the list is only refreshed at startup.
+#[tauri::command]
+pub async fn set_games_list_refresh_interval(state: tauri::State<'_, ScannerState>, hours: u64) -> Result<(), String> {
+    if hours > 24 * 365 {
+        return Err("refresh interval must be at most a year".to_string());
+    }
+    update_settings(&state, move |settings| settings.list_refresh_hours = hours).await?;
+    state.list_refresh.notify_one();
+    Ok(())
+}
+
+/// Returns every running game along with the current primary activity.
+#[tauri::command]
+pub async fn get_running_games(state: tauri::State<'_, ScannerState>) -> Result<GameActivitySet, String> {
+    state.query(Scanner::running_games).await
+}
+
+/// Returns the primary activity, so the frontend can restore it after a reload.
+#[tauri::command]
+pub async fn get_current_activity(state: tauri::State<'_, ScannerState>) -> Result<Option<GameActivity>, String> {
+    state.query(|scanner| scanner.primary().map(GameActivity::started)).await
+}
+
+/// Scans immediately instead of waiting for the next interval and returns the result.
+#[tauri::command]
+pub async fn rescan_games(state: tauri::State<'_, ScannerState>) -> Result<GameActivitySet, String> {
+    state.rescan().await
+}
+
+/// Reports `name` as the current activity regardless of what is detected,
+/// until [`clear_manual_activity`] is called. `id` defaults to one derived from the name.
+#[tauri::command]
+pub fn set_manual_activity(
+    state: tauri::State<'_, ScannerState>,
+    name: String,
+    id: Option<String>,
+) -> Result<GameActivity, String> {
+    if name.trim().is_empty() {
+        return Err("activity name must not be empty".to_string());
+    }
+    let game = RunningGame {
+        id: id.unwrap_or_else(|| format!("manual:{}", custom::slug(&name))),
+        name: name.trim().to_string(),
+        started_at: unix_now(),
+        ..RunningGame::default()
+    };
+    let activity = GameActivity::started(&game);
+    state.update(move |scanner| scanner.set_manual_activity(Some(game)))?;
+    Ok(activity)
+}
+
+/// Drops the manual activity and goes back to reporting the detected one.
+#[tauri::command]
+pub fn clear_manual_activity(state: tauri::State<'_, ScannerState>) -> Result<(), String> {
+    state.update(|scanner| scanner.set_manual_activity(None))
+}
+
+/// Sets how the primary activity is chosen when several games are running.
+/// `pinned_game` is the id of the game preferred by [`PrimaryPolicy::Pinned`].
+#[tauri::command]
+pub async fn set_primary_policy(
+    state: tauri::State<'_, ScannerState>,
+    policy: PrimaryPolicy,
+    pinned_game: Option<String>,
+) -> Result<(), String> {
+    update_settings(&state, move |settings| {
+        settings.primary_policy = policy;
+        settings.pinned_game = pinned_game;
+    })
+    .await?;
+    log::info!("Primary activity policy set to: {:?}", policy);
+    state.wake()
+}
+
+/// Sets whether games detected through a launcher such as Steam are reported
+/// with low priority or not at all.
+#[tauri::command]
+pub async fn set_launcher_policy(state: tauri::State<'_, ScannerState>, policy: LauncherPolicy) -> Result<(), String> {
+    update_settings(&state, move |settings| settings.launcher_policy = policy).await?;
+    log::info!("Launcher policy set to: {:?}", policy);
+    state.wake()
+}
+
+/// Enables or disables recording play sessions to the local history.
+#[tauri::command]
+pub async fn set_history_enabled(state: tauri::State<'_, ScannerState>, enabled: bool) -> Result<(), String> {
+    update_settings(&state, move |settings| settings.record_history = enabled).await
+}
+
+/// Total recorded playtime per game, most played first.
+#[tauri::command]
+pub async fn get_playtime_totals(app: AppHandle) -> Vec<GameTotal> {
+    history::totals_per_game(&load_history(app).await)
+}
+
+/// Recorded playtime per game per day since the Unix timestamp `since`.
+/// `utc_offset_minutes` places day boundaries in the user's time zone.
+#[tauri::command]
+pub async fn get_playtime_by_day(app: AppHandle, since: Option<u64>, utc_offset_minutes: Option<i32>) -> Vec<DayTotal> {
+    history::totals_per_day(&load_history(app).await, since.unwrap_or(0), utc_offset_minutes.unwrap_or(0))
+}
+
+/// The most recently finished play sessions, newest first.
+#[tauri::command]
+pub async fn get_recent_sessions(app: AppHandle, limit: Option<usize>) -> Vec<Session> {
+    history::recent_sessions(&load_history(app).await, limit.unwrap_or(20))
+}
+
+/// Reads the whole play history, which only grows, on a blocking thread.
+async fn load_history(app: AppHandle) -> Vec<Session> {
+    match history::history_path(&app) {
+        Some(path) => tauri::async_runtime::spawn_blocking(move || history::load(&path))
+            .await
+            .unwrap_or_default(),
+        None => Vec::new(),
+    }
+}
+
+/// Sets how many consecutive scans must see a game before it counts as
+/// started, and miss it before it counts as stopped.
+#[tauri::command]
+pub async fn set_scanner_hysteresis(
+    state: tauri::State<'_, ScannerState>,
+    start_scans: u32,
+    stop_scans: u32,
+) -> Result<(), String> {
+    if start_scans == 0 || stop_scans == 0 {
+        return Err("scan counts must be at least 1".to_string());
+    }
+    update_settings(&state, move |settings| {
+        settings.hysteresis = Hysteresis {
+            start_scans,
+            stop_scans,
+        }
+    })
+    .await
+}
+
+/// Sets the time between scans. With `adaptive` set, the scanner runs faster
+/// for a few scans after a change or when the app gains focus, and slower on
+/// battery power or while the user is idle.
+#[tauri::command]
+pub async fn set_scanner_interval(
+    state: tauri::State<'_, ScannerState>,
+    seconds: u64,
+    adaptive: bool,
+) -> Result<(), String> {
+    if !(1..=300).contains(&seconds) {
+        return Err("scan interval must be between 1 and 300 seconds".to_string());
+    }
+    update_settings(&state, move |settings| settings.scan_interval = ScanInterval { seconds, adaptive }).await?;
+    log::info!("Scan interval set to {}s (adaptive: {})", seconds, adaptive);
+    state.wake()
+}
+
+/// Timings of recent scans, for tuning the scan interval.
+#[tauri::command]
+pub async fn get_scanner_metrics(state: tauri::State<'_, ScannerState>) -> Result<ScanMetrics, String> {
+    state.query(|scanner| scanner.metrics.clone()).await
+}
+
+/// Enables waking the scanner on process start and exit events. Only Linux
+/// provides these, and only with `CAP_NET_ADMIN`; elsewhere the scanner keeps
+/// polling. Returns whether the event listener is running.
+#[tauri::command]
+pub async fn set_process_events(state: tauri::State<'_, ScannerState>, enabled: bool) -> Result<bool, String> {
+    state
+        .configure(move |scanner| {
+            scanner.update_settings(|settings| settings.process_events = enabled)?;
+            Ok(scanner.apply_process_events())
+        })
+        .await?
+}
+
+/// Enables watching for games installed through Lutris, Heroic and Bottles,
+/// named from each launcher's local library.
+#[tauri::command]
+pub async fn set_launcher_libraries(state: tauri::State<'_, ScannerState>, enabled: bool) -> Result<(), String> {
+    state
+        .configure(move |scanner| {
+            scanner.update_settings(|settings| settings.launcher_libraries = enabled)?;
+            scanner.apply_launcher_libraries();
+            scanner.rebuild_watch_list();
+            scanner.wake();
+            Ok(())
+        })
+        .await?
+}
+
+/// Called from the window event handler when `window` gains or loses focus.
+pub fn set_app_focused(window: &tauri::Window, focused: bool) {
+    let state = window.state::<ScannerState>();
+    let _ = state.update(move |scanner| {
+        let was_focused = std::mem::replace(&mut scanner.app_focused, focused);
+        // Scan right away when the user comes back to the app
+        if focused && !was_focused && scanner.settings.scan_interval.adaptive {
+            scanner.wake();
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_ignore_list(state: tauri::State<'_, ScannerState>) -> Result<IgnoreList, String> {
+    state.query(|scanner| scanner.settings.ignored.clone()).await
 }
 
-/// Shared state for the scanner's watch list and current detected game.
-pub struct ScannerState {
-    pub watch_list: Mutex<Vec<DetectableGame>>,
-    pub current_game: Mutex<Option<String>>,
-    pub is_enabled: Mutex<bool>,
-    pub notify: Arc<Notify>,
+/// Hides the game with `id` from shared activity, or shares it again. Hidden
+/// games are still recorded to the local history.
+#[tauri::command]
+pub async fn set_game_ignored(state: tauri::State<'_, ScannerState>, id: String, ignored: bool) -> Result<(), String> {
+    update_settings(&state, move |settings| {
+        if ignored {
+            settings.ignored.games.insert(id);
+        } else {
+            settings.ignored.games.remove(&id);
+        }
+    })
+    .await?;
+    state.wake()
 }
 
+/// Hides every game in `category` from shared activity, or shares them again.
+/// Categories are the themes of the detectable list, such as `horror`, and
+/// `custom` for user-defined games.
 #[tauri::command]
-pub fn set_scanner_enabled(state: tauri::State<'_, Arc<ScannerState>>, enabled: bool) {
-    let mut is_enabled = state.is_enabled.lock().unwrap();
-    if *is_enabled != enabled {
-        *is_enabled = enabled;
-        println!("[game_scanner] Scanner state set to: {}", enabled);
-        // Wake up the loop immediately if enabled, or to process disable
-        state.notify.notify_one();
+pub async fn set_category_ignored(
+    state: tauri::State<'_, ScannerState>,
+    category: String,
+    ignored: bool,
+) -> Result<(), String> {
+    let category = category.trim().to_lowercase();
+    if category.is_empty() {
+        return Err("category must not be empty".to_string());
     }
+    update_settings(&state, move |settings| {
+        if ignored {
+            settings.ignored.categories.insert(category);
+        } else {
+            settings.ignored.categories.remove(&category);
+        }
+    })
+    .await?;
+    state.wake()
+}
+
+/// Enables matching Windows executables against processes running under Wine or Proton.
+#[tauri::command]
+pub async fn set_compatibility_layer(state: tauri::State<'_, ScannerState>, enabled: bool) -> Result<(), String> {
+    update_settings(&state, move |settings| settings.compatibility_layer = enabled).await?;
+    log::info!("Compatibility layer matching set to: {}", enabled);
+    state.wake()
+}
+
+/// Applies `change` to the settings on the scanner task and persists them.
+async fn update_settings(
+    state: &ScannerState,
+    change: impl FnOnce(&mut ScannerSettings) + Send + 'static,
+) -> Result<(), String> {
+    state.configure(move |scanner| scanner.update_settings(change)).await?
+}
+
+#[tauri::command]
+pub async fn list_custom_games(state: tauri::State<'_, ScannerState>) -> Result<Vec<DetectableGame>, String> {
+    state.query(|scanner| scanner.custom_games.clone()).await
+}
+
+/// Adds a user-defined game, or replaces the custom game with the same name.
+#[tauri::command]
+pub async fn add_custom_game(
+    state: tauri::State<'_, ScannerState>,
+    name: String,
+    executables: Vec<String>,
+) -> Result<DetectableGame, String> {
+    if name.trim().is_empty() {
+        return Err("game name must not be empty".to_string());
+    }
+    if executables.iter().all(|exe| exe.trim().is_empty()) {
+        return Err("at least one executable is required".to_string());
+    }
+    let executables: Vec<String> = executables.into_iter().filter(|exe| !exe.trim().is_empty()).collect();
+
+    let game = custom::custom_game(&name, &executables);
+    let added = game.clone();
+    state
+        .configure(move |scanner| {
+            scanner.update_custom_games(|games| {
+                games.retain(|existing| existing.id != added.id);
+                games.push(added);
+            })
+        })
+        .await??;
+    log::info!("Added custom game '{}'", game.name);
+    Ok(game)
+}
+
+/// Removes a user-defined game. Returns whether a game with `id` existed.
+#[tauri::command]
+pub async fn remove_custom_game(state: tauri::State<'_, ScannerState>, id: String) -> Result<bool, String> {
+    state
+        .configure(move |scanner| {
+            let mut removed = false;
+            scanner.update_custom_games(|games| {
+                let before = games.len();
+                games.retain(|game| game.id != id);
+                removed = games.len() != before;
+            })?;
+            Ok(removed)
+        })
+        .await?
+}
+
+/// Location of the games list cache for `app`, if the data directory is known.
+fn cache_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join(cache::CACHE_FILE_NAME))
 }
 
-/// Fetches the Detectable Games list from Discord and filters it for the current OS.
-async fn fetch_detectable_games() -> Result<Vec<DetectableGame>, reqwest::Error> {
-    let url = "https://discord.com/api/v9/applications/detectable";
-    let client = reqwest::Client::new();
-    let response = client.get(url).send().await?;
-    let games: Vec<DetectableGame> = response.json().await?;
+/// Loads the games list from `source`. Remote sources are fetched conditionally against `cached`.
+async fn load_games(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    source: &GamesSource,
+    cached: Option<&GamesCache>,
+) -> Result<FetchOutcome, SourceError> {
+    match source.locate(app)? {
+        SourceLocation::Remote(url) => cache::fetch_detectable_games(client, &url, cached).await,
+        SourceLocation::Local(path) => Ok(FetchOutcome::Modified(GamesCache {
+            source: source.to_string(),
+            games: source::read_games_file(&path)?,
+            fetched_at: unix_now(),
+            ..GamesCache::default()
+        })),
+    }
+}
 
-    // We want to detect games regardless of OS (e.g., Windows games via Crossover on Mac)
-    let mut filtered_games: Vec<DetectableGame> = games;
+/// The games list for `source` cached at `path`, if there is one.
+fn cached_games(path: Option<&Path>, source: &GamesSource) -> Option<GamesCache> {
+    let source = source.to_string();
+    path.and_then(GamesCache::load).filter(|cache| cache.source == source)
+}
 
-    // Push the mock calculator game for testing
-    filtered_games.push(DetectableGame {
+/// Games that only exist in debug builds, for exercising detection without a real game.
+#[cfg(debug_assertions)]
+fn debug_games() -> Vec<DetectableGame> {
+    vec![DetectableGame {
         id: "mock_calc_123".to_string(),
         name: "Calculator".to_string(),
         executables: Some(vec![GameExecutable {
             os: "all".to_string(),
             name: "Calculator".to_string(),
-        }])
-    });
-
-    Ok(filtered_games)
+            ..GameExecutable::default()
+        }]),
+        ..DetectableGame::default()
+    }]
 }
 
-/// Starts the background game scanner loop.
-pub fn start(app: AppHandle, state: Arc<ScannerState>) {
-    tauri::async_runtime::spawn(async move {
-        let mut sys = System::new_all();
-        let mut fetch_retry_interval = Duration::from_secs(5);
+/// Refreshes the watch list from the configured source, retrying network failures
+/// with backoff. Errors that a retry can't fix, such as a malformed payload, are
+/// reported once and the current watch list is kept. Fails only if the scanner
+/// has stopped.
+async fn refresh_watch_list(app: &AppHandle, state: &ScannerState, cached: Option<GamesCache>) -> Result<(), String> {
+    let source = state.query(|scanner| scanner.settings.games_source.clone()).await?;
+    let mut fetch_retry_interval = Duration::from_secs(5);
+
+    loop {
+        state.update(|scanner| scanner.update_status(|status| status.fetching_list = true))?;
+        let result = load_games(app, &state.http, &source, cached.as_ref()).await;
+        let error = result.as_ref().err().map(|e| {
+            let retry_at = e.is_retryable().then(|| unix_now() + fetch_retry_interval.as_secs());
+            list_error(e, retry_at)
+        });
+        let fresh = match result {
+            Ok(FetchOutcome::Modified(fresh)) => Some(fresh),
+            _ => None,
+        };
 
-        // Intial fetch of the games list
-        loop {
-            match fetch_detectable_games().await {
-                Ok(games) => {
-                    println!("[game_scanner] Successfully fetched {} detectable games", games.len());
-                    *state.watch_list.lock().unwrap() = games;
-                    break;
+        let retryable = error.as_ref().map_or(false, |e| e.retry_at.is_some());
+
+        let expected = source.clone();
+        let applied = state
+            .configure(move |scanner| {
+                // The source may have been switched while this request was in flight.
+                if scanner.settings.games_source != expected {
+                    scanner.update_status(|status| status.fetching_list = false);
+                    return false;
                 }
-                Err(e) => {
-                    println!("[game_scanner] Failed to fetch games: {}. Retrying in {:?}...", e, fetch_retry_interval);
-                    tokio::time::sleep(fetch_retry_interval).await;
-                    // Cap retry interval at 60 seconds
-                    if fetch_retry_interval < Duration::from_secs(60) {
-                        fetch_retry_interval *= 2;
+                match fresh {
+                    Some(fresh) => {
+                        log::info!(
+                            "Successfully fetched {} detectable games from {}",
+                            fresh.games.len(),
+                            expected
+                        );
+                        scanner.apply_games_list(fresh);
                     }
+                    None if error.is_none() => log::info!("Cached games list is up to date"),
+                    None => {}
                 }
-            }
+                scanner.update_status(|status| {
+                    status.fetching_list = false;
+                    status.list_error = error;
+                });
+                true
+            })
+            .await?;
+
+        if !applied || !retryable {
+            // Done, or retrying won't help; keep the current list until the source changes.
+            return Ok(());
         }
+        tokio::time::sleep(fetch_retry_interval).await;
+        // Cap retry interval at 60 seconds
+        if fetch_retry_interval < Duration::from_secs(60) {
+            fetch_retry_interval *= 2;
+        }
+    }
+}
 
+/// Refreshes the games list right away and then every `list_refresh_hours`,
+/// until the scanner stops or is disabled.
+async fn refresh_games_periodically(app: AppHandle, state: ScannerState, cached: Option<GamesCache>) -> Result<(), String> {
+    refresh_watch_list(&app, &state, cached).await?;
 
+    loop {
+        let hours = state.query(|scanner| scanner.settings.list_refresh_hours).await?;
+        if hours == 0 {
+            state.list_refresh.notified().await;
+            continue;
+        }
+        let interval = Duration::from_secs(hours * 60 * 60);
+        // A changed interval restarts the wait.
+        if tokio::time::timeout(interval, state.list_refresh.notified()).await.is_ok() {
+            continue;
+        }
 
-        loop {
-            // Check enabled state first
-            let is_enabled = *state.is_enabled.lock().unwrap();
+        let source = state.query(|scanner| scanner.settings.games_source.clone()).await?;
+        log::info!("Refreshing games list from {}", source);
+        let cached = cached_games(cache_path(&app).as_deref(), &source);
+        refresh_watch_list(&app, &state, cached).await?;
+    }
+}
 
-            if !is_enabled {
-                // If disabled, wait indefinitely for a notification (enable command)
-                println!("[game_scanner] Scanner disabled, waiting...");
-                state.notify.notified().await;
-                println!("[game_scanner] Scanner woke up!");
-                continue;
-            }
+/// How long app exit waits for the scanner to wrap up.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
 
-            // If enabled, proceed with scan
-            // Use specific refresh kind to prevent MacOS Objective-C null pointer panic 
-            // from trying to fetch restricted process environments.
-            sys.refresh_processes_specifics(
-                sysinfo::ProcessRefreshKind::new()
-            );
-
-            let watch_list = state.watch_list.lock().unwrap().clone();
-            let previous_game = state.current_game.lock().unwrap().clone();
-
-            let mut detected_name: Option<String> = None;
-            let mut detected_exe: Option<String> = None;
-
-            // Check each process against the watch list
-            for (_, process) in sys.processes() {
-                let process_name = process.name();
-                
-                for game in &watch_list {
-                    if let Some(executables) = &game.executables {
-                        for exe in executables {
-                            // Exact match (case insensitive), stripping '.exe' to match cleanly across OS APIs
-                            let clean_exe = exe.name.trim_end_matches(".exe");
-                            let clean_proc = process_name.trim_end_matches(".exe");
-                            
-                            if clean_exe.eq_ignore_ascii_case(clean_proc) {
-                                println!("[game_scanner] Matched process '{}' to executable '{}' for game '{}'", process_name, exe.name, game.name);
-                                detected_name = Some(game.name.clone());
-                                detected_exe = Some(exe.name.clone());
-                                break;
-                            }
-                        }
-                    }
-                    if detected_name.is_some() {
-                        break;
-                    }
-                }
-                if detected_name.is_some() {
-                    break;
-                }
-            }
+/// Current time as Unix seconds.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
-            // Only emit on state changes
-            match (&previous_game, &detected_name) {
-                (None, Some(name)) => {
-                    // Game just started
-                    println!("[game_scanner] Detected: {}", name);
-                    let _ = app.emit_all(
-                        "game-activity",
-                        GameActivity {
-                            name: name.clone(),
-                            executable_name: detected_exe.clone(),
-                            is_running: true,
-                        },
-                    );
-                }
-                (Some(prev), None) => {
-                    // Game just stopped
-                    println!("[game_scanner] Stopped: {}", prev);
-                    let _ = app.emit_all(
-                        "game-activity",
-                        GameActivity {
-                            name: prev.clone(),
-                            executable_name: None,
-                            is_running: false,
-                        },
-                    );
-                }
-                (Some(prev), Some(name)) if prev != name => {
-                    // Switched games
-                    println!("[game_scanner] Switched: {} -> {}", prev, name);
-                    let _ = app.emit_all(
-                        "game-activity",
-                        GameActivity {
-                            name: name.clone(),
-                            executable_name: detected_exe.clone(),
-                            is_running: true,
-                        },
-                    );
-                }
-                _ => {
-                    // No change — don't emit
-                }
-            }
+/// Reads the persisted scanner settings. Called from `main` before the app is
+/// built, so the scanner starts in the state the user left it in.
+pub fn load_settings(config: &tauri::Config) -> ScannerSettings {
+    tauri::api::path::app_config_dir(config)
+        .map(|dir| ScannerSettings::load(&dir.join(settings::SETTINGS_FILE_NAME)))
+        .unwrap_or_default()
+}
 
-            // Update current state
-            *state.current_game.lock().unwrap() = detected_name;
+/// The scanner's [`Host`]: the Tauri app.
+struct AppHost(AppHandle);
 
-            // Wait for 15s OR a notification (e.g. disable command or instant re-scan)
-            if tokio::time::timeout(Duration::from_secs(15), state.notify.notified()).await.is_ok() {
-                println!("[game_scanner] Scan interrupt received");
-            }
-        }
+impl Host for AppHost {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.0.emit_all(event, payload);
+    }
+
+    fn refresh_games_list(
+        &self,
+        scanner: ScannerState,
+        cached: Option<GamesCache>,
+    ) -> tauri::async_runtime::JoinHandle<Result<(), String>> {
+        tauri::async_runtime::spawn(refresh_games_periodically(self.0.clone(), scanner, cached))
+    }
+}
+
+/// Where the scanner keeps its files for `app`.
+fn scanner_paths(app: &AppHandle) -> ScannerPaths {
+    ScannerPaths {
+        settings: settings::settings_path(app),
+        custom_games: custom::store_path(app),
+        emulators: emulator::emulators_path(app),
+        cache: cache_path(app),
+        history: history::history_path(app),
+    }
+}
+
+/// Starts the background game scanner task, reading commands sent through `state`.
+/// If the task panics, the frontend is told through `scanner-status`.
+pub fn start(app: AppHandle, state: ScannerState, inbox: ScannerInbox) {
+    let (host, paths, handle) = (AppHost(app.clone()), scanner_paths(&app), state.clone());
+    let task = tauri::async_runtime::spawn(async move {
+        // Reading the cached list takes a while, so it happens here rather
+        // than holding up the window.
+        let mut scanner = Scanner::new(Box::new(host), paths, handle, inbox, Box::new(SysinfoSource::new()));
+        scanner.load();
+        scanner.run().await
     });
+    state.set_task(tauri::async_runtime::spawn(async move {
+        if let Err(e) = task.await {
+            log::error!("Scanner task failed: {}", e);
+            let status = ScannerStatus::Error {
+                kind: ErrorKind::Crashed,
+                message: format!("game scanner stopped unexpectedly: {}", e),
+                retry_at: None,
+            };
+            let _ = app.emit_all("scanner-status", status);
+        }
+    }));
+}
+
+/// Stops the scanner task on app exit: running games are reported as stopped
+/// and their sessions recorded before the app goes away.
+pub fn stop(app: &AppHandle) {
+    let state = app.state::<ScannerState>();
+    let stopped = tauri::async_runtime::block_on(tokio::time::timeout(SHUTDOWN_TIMEOUT, state.shutdown()));
+    if stopped.is_err() {
+        log::warn!("Timed out waiting for the scanner to stop");
+    }
 }
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A game matched during a single scan.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Detection {
+    pub id: String,
+    pub name: String,
+    pub executable_name: Option<String>,
+    /// When the matched process started, if the OS reports it.
+    pub started_at: Option<u64>,
+    /// The game is on the ignore list: tracked, but not shared.
+    pub hidden: bool,
+    /// Only launcher executables of the game matched.
+    pub is_launcher: bool,
+    pub aliases: Vec<String>,
+    pub icon_hash: Option<String>,
+    /// The emulator the game was detected in.
+    pub emulator: Option<String>,
+}
+
+/// A detected game that is currently running.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunningGame {
+    pub id: String,
+    pub name: String,
+    pub executable_name: Option<String>,
+    /// Unix timestamp (seconds) the game started at: the process start time
+    /// when known, otherwise the time it was first detected.
+    pub started_at: u64,
+    pub hidden: bool,
+    pub is_launcher: bool,
+    pub aliases: Vec<String>,
+    pub icon_hash: Option<String>,
+    pub emulator: Option<String>,
+}
+
+/// How the primary activity is chosen when several games are running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryPolicy {
+    /// The game that started last.
+    MostRecent,
+    /// The user's pinned game while it runs, otherwise the most recent one.
+    Pinned,
+    /// The game that has been running the longest.
+    LongestRunning,
+}
+
+impl Default for PrimaryPolicy {
+    fn default() -> Self {
+        PrimaryPolicy::MostRecent
+    }
+}
+
+/// How games detected only through a launcher executable, such as Steam or
+/// Battle.net, are treated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LauncherPolicy {
+    /// Report launchers, but only as the primary activity when no game is running.
+    Deprioritize,
+    /// Don't detect launchers at all.
+    Exclude,
+}
+
+impl Default for LauncherPolicy {
+    fn default() -> Self {
+        LauncherPolicy::Deprioritize
+    }
+}
+
+/// Games that started or stopped between two scans, and running games that
+/// were added to or removed from the ignore list.
+#[derive(Debug, Default)]
+pub struct ActivityChanges {
+    pub started: Vec<RunningGame>,
+    pub stopped: Vec<RunningGame>,
+    pub hidden: Vec<RunningGame>,
+    pub shown: Vec<RunningGame>,
+}
+
+/// The set of games currently running, keyed by game id so that iteration
+/// order doesn't depend on the order processes were listed in.
+#[derive(Debug, Default)]
+pub struct ActivitySet {
+    running: BTreeMap<String, RunningGame>,
+}
+
+impl ActivitySet {
+    /// Reconciles the set with the games found by the latest scan.
+    pub fn update(&mut self, detected: Vec<Detection>, now: u64) -> ActivityChanges {
+        let mut detected: BTreeMap<String, Detection> = detected
+            .into_iter()
+            .map(|detection| (detection.id.clone(), detection))
+            .collect();
+
+        let mut changes = ActivityChanges::default();
+        let stopped: Vec<String> = self
+            .running
+            .keys()
+            .filter(|id| !detected.contains_key(*id))
+            .cloned()
+            .collect();
+        for id in stopped {
+            changes.stopped.extend(self.running.remove(&id));
+        }
+
+        detected.retain(|id, detection| match self.running.get_mut(id) {
+            Some(game) => {
+                // Closing the game while its launcher keeps running isn't a stop.
+                game.is_launcher = detection.is_launcher;
+                if game.hidden != detection.hidden {
+                    game.hidden = detection.hidden;
+                    if game.hidden {
+                        changes.hidden.push(game.clone());
+                    } else {
+                        changes.shown.push(game.clone());
+                    }
+                }
+                false
+            }
+            None => true,
+        });
+        for (id, detection) in detected {
+            let game = RunningGame {
+                id: id.clone(),
+                name: detection.name,
+                executable_name: detection.executable_name,
+                started_at: detection.started_at.map_or(now, |started_at| started_at.min(now)),
+                hidden: detection.hidden,
+                is_launcher: detection.is_launcher,
+                aliases: detection.aliases,
+                icon_hash: detection.icon_hash,
+                emulator: detection.emulator,
+            };
+            changes.started.push(game.clone());
+            self.running.insert(id, game);
+        }
+
+        changes
+    }
+
+    pub fn games(&self) -> impl Iterator<Item = &RunningGame> {
+        self.running.values()
+    }
+
+    /// Running games that aren't on the ignore list.
+    pub fn visible_games(&self) -> impl Iterator<Item = &RunningGame> {
+        self.running.values().filter(|game| !game.hidden)
+    }
+
+    /// The game to report as the primary activity under `policy`. Hidden games
+    /// are never primary, and launchers only when no actual game is running.
+    pub fn primary(&self, policy: PrimaryPolicy, pinned: Option<&str>) -> Option<&RunningGame> {
+        let only_launchers = self.visible_games().all(|game| game.is_launcher);
+        let candidates = || self.visible_games().filter(move |game| only_launchers || !game.is_launcher);
+        let most_recent = || candidates().max_by_key(|game| game.started_at);
+        match policy {
+            PrimaryPolicy::MostRecent => most_recent(),
+            PrimaryPolicy::Pinned => pinned
+                .and_then(|id| self.running.get(id))
+                .filter(|game| !game.hidden)
+                .or_else(most_recent),
+            // `min_by_key` keeps the first of equal keys; ties go to the lowest id.
+            PrimaryPolicy::LongestRunning => candidates().min_by_key(|game| game.started_at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(id: &str) -> Detection {
+        Detection {
+            id: id.to_string(),
+            name: format!("Game {}", id),
+            executable_name: Some(format!("{}.exe", id)),
+            ..Detection::default()
+        }
+    }
+
+    fn ids(games: &[RunningGame]) -> Vec<&str> {
+        games.iter().map(|game| game.id.as_str()).collect()
+    }
+
+    #[test]
+    fn update_reports_each_start_and_stop_once() {
+        let mut set = ActivitySet::default();
+
+        let changes = set.update(vec![detection("b"), detection("a")], 100);
+        assert_eq!(ids(&changes.started), vec!["a", "b"]);
+        assert!(changes.stopped.is_empty());
+
+        let changes = set.update(vec![detection("a"), detection("b")], 200);
+        assert!(changes.started.is_empty() && changes.stopped.is_empty());
+
+        let changes = set.update(vec![detection("b"), detection("c")], 300);
+        assert_eq!(ids(&changes.started), vec!["c"]);
+        assert_eq!(ids(&changes.stopped), vec!["a"]);
+
+        let running: Vec<_> = set.games().map(|game| (game.id.as_str(), game.started_at)).collect();
+        assert_eq!(running, vec![("b", 100), ("c", 300)]);
+    }
+
+    #[test]
+    fn process_start_time_is_used_when_known() {
+        let mut set = ActivitySet::default();
+        let mut early = detection("a");
+        early.started_at = Some(40);
+        let mut future = detection("b");
+        future.started_at = Some(500);
+
+        let changes = set.update(vec![early, future], 100);
+        let started: Vec<_> = changes.started.iter().map(|game| game.started_at).collect();
+        assert_eq!(started, vec![40, 100]);
+    }
+
+    #[test]
+    fn primary_follows_policy() {
+        let mut set = ActivitySet::default();
+        set.update(vec![detection("a")], 100);
+        set.update(vec![detection("a"), detection("b")], 200);
+
+        let primary = |policy, pinned| set.primary(policy, pinned).map(|game| game.id.clone());
+        assert_eq!(primary(PrimaryPolicy::MostRecent, None).as_deref(), Some("b"));
+        assert_eq!(primary(PrimaryPolicy::LongestRunning, None).as_deref(), Some("a"));
+        assert_eq!(primary(PrimaryPolicy::Pinned, Some("a")).as_deref(), Some("a"));
+        assert_eq!(primary(PrimaryPolicy::Pinned, Some("z")).as_deref(), Some("b"));
+
+        set.update(Vec::new(), 300);
+        assert!(set.primary(PrimaryPolicy::MostRecent, None).is_none());
+    }
+
+    #[test]
+    fn hidden_games_are_tracked_but_never_primary() {
+        let mut set = ActivitySet::default();
+        let mut hidden = detection("b");
+        hidden.hidden = true;
+        set.update(vec![detection("a"), hidden.clone()], 100);
+
+        assert_eq!(set.games().count(), 2);
+        assert_eq!(set.visible_games().count(), 1);
+        assert_eq!(set.primary(PrimaryPolicy::MostRecent, None).map(|game| game.id.as_str()), Some("a"));
+        assert_eq!(set.primary(PrimaryPolicy::Pinned, Some("b")).map(|game| game.id.as_str()), Some("a"));
+
+        // Taking a running game off the ignore list shows it without restarting it.
+        let changes = set.update(vec![detection("a"), detection("b")], 200);
+        assert_eq!(ids(&changes.shown), vec!["b"]);
+        assert!(changes.started.is_empty() && changes.hidden.is_empty());
+        let changes = set.update(vec![detection("a"), hidden], 300);
+        assert_eq!(ids(&changes.hidden), vec!["b"]);
+    }
+
+    #[test]
+    fn launchers_are_primary_only_without_a_game() {
+        let mut set = ActivitySet::default();
+        let mut steam = detection("steam");
+        steam.is_launcher = true;
+        set.update(vec![detection("a")], 100);
+        set.update(vec![detection("a"), steam.clone()], 200);
+
+        let primary = |set: &ActivitySet, policy| set.primary(policy, None).map(|game| game.id.clone());
+        assert_eq!(primary(&set, PrimaryPolicy::MostRecent).as_deref(), Some("a"));
+        assert_eq!(set.primary(PrimaryPolicy::Pinned, Some("steam")).map(|game| game.id.as_str()), Some("steam"));
+
+        set.update(vec![steam], 300);
+        assert_eq!(primary(&set, PrimaryPolicy::MostRecent).as_deref(), Some("steam"));
+        assert_eq!(primary(&set, PrimaryPolicy::LongestRunning).as_deref(), Some("steam"));
+    }
+}
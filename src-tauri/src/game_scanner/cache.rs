@@ -0,0 +1,234 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::source::{self, SourceError};
+use super::storage;
+use super::{unix_now, DetectableGame};
+
+/// File name of the cached detectable games list inside the app data directory.
+pub const CACHE_FILE_NAME: &str = "detectable_games.json";
+
+/// Longest a games list request may take, download included.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest to wait for a connection to the games list server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The last successfully fetched detectable games list, persisted to disk so
+/// scanning can start before (or without) a network round trip.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct GamesCache {
+    /// The URL the list was fetched from.
+    #[serde(default)]
+    pub source: String,
+    pub games: Vec<DetectableGame>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Unix timestamp (seconds) of the fetch that produced `games`.
+    pub fetched_at: u64,
+}
+
+/// The client for games list requests. Its timeouts make a stalled server
+/// fail the request instead of leaving the refresh waiting forever.
+pub fn http_client() -> reqwest::Client {
+    client_with_timeout(REQUEST_TIMEOUT)
+}
+
+fn client_with_timeout(timeout: Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .connect_timeout(CONNECT_TIMEOUT.min(timeout))
+        .build()
+        .expect("failed to initialize the HTTP client")
+}
+
+/// Result of a conditional fetch against the games list endpoint.
+pub enum FetchOutcome {
+    /// The server returned a fresh list.
+    Modified(GamesCache),
+    /// The server confirmed the cached list is still current.
+    NotModified,
+}
+
+impl GamesCache {
+    /// Reads the cache from `path`, returning `None` if it is missing or unreadable.
+    pub fn load(path: &Path) -> Option<GamesCache> {
+        let data = fs::read(path).ok()?;
+        match serde_json::from_slice(&data) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                log::warn!("Ignoring corrupt games cache {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Writes the cache to `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::write_json_atomic(path, self)
+    }
+}
+
+/// Fetches the detectable games list from `url`, sending the validators of
+/// `cached` so an unchanged list costs a `304 Not Modified` instead of a full download.
+pub async fn fetch_detectable_games(
+    client: &reqwest::Client,
+    url: &str,
+    cached: Option<&GamesCache>,
+) -> Result<FetchOutcome, SourceError> {
+    // Validators from a different source would be meaningless to this server.
+    let cached = cached.filter(|cache| cache.source == url);
+
+    let mut request = client.get(url);
+    if let Some(cache) = cached {
+        if let Some(etag) = &cache.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cache.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED && cached.is_some() {
+        return Ok(FetchOutcome::NotModified);
+    }
+    let response = response.error_for_status()?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let games = source::parse_games(&response.bytes().await?)?;
+
+    Ok(FetchOutcome::Modified(GamesCache {
+        source: url.to_string(),
+        games,
+        etag,
+        last_modified,
+        fetched_at: unix_now(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use crate::game_scanner::test_support::temp_dir;
+
+    const INVALID_JSON: &str = r#"[{"id":"1","executables":[]}]"#;
+    const GAMES_JSON: &str =
+        r#"[{"id":"1","name":"Test Game","executables":[{"os":"win32","name":"test.exe"}]}]"#;
+
+    /// Serves `requests` HTTP requests, answering `304` when the client
+    /// presents the `"v1"` ETag and `body` otherwise.
+    fn serve(requests: usize, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut conditional = false;
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if line.to_ascii_lowercase().starts_with("if-none-match: \"v1\"") {
+                        conditional = true;
+                    }
+                }
+                let response = if conditional {
+                    "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nETag: \"v1\"\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}/detectable", addr)
+    }
+
+    #[test]
+    fn load_ignores_missing_and_corrupt_files() {
+        let dir = temp_dir("corrupt");
+        assert!(GamesCache::load(&dir.join(CACHE_FILE_NAME)).is_none());
+
+        fs::write(dir.join(CACHE_FILE_NAME), b"{ not json").unwrap();
+        assert!(GamesCache::load(&dir.join(CACHE_FILE_NAME)).is_none());
+    }
+
+    #[test]
+    fn fetch_stores_validators_and_honors_not_modified() {
+        let url = serve(2, GAMES_JSON);
+        let client = http_client();
+
+        let fresh = tauri::async_runtime::block_on(fetch_detectable_games(&client, &url, None))
+            .unwrap();
+        let cache = match fresh {
+            FetchOutcome::Modified(cache) => cache,
+            FetchOutcome::NotModified => panic!("expected a fresh list"),
+        };
+        assert_eq!(cache.games.len(), 1);
+        assert_eq!(cache.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(
+            cache.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert!(cache.fetched_at > 0);
+        assert_eq!(cache.source, url);
+
+        let revalidated =
+            tauri::async_runtime::block_on(fetch_detectable_games(&client, &url, Some(&cache)))
+                .unwrap();
+        assert!(matches!(revalidated, FetchOutcome::NotModified));
+    }
+
+    #[test]
+    fn fetch_reports_schema_errors() {
+        let url = serve(1, INVALID_JSON);
+        let client = http_client();
+
+        let result = tauri::async_runtime::block_on(fetch_detectable_games(&client, &url, None));
+        match result {
+            Err(e @ SourceError::Parse(_)) => assert!(!e.is_retryable()),
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn fetch_gives_up_on_a_stalled_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/detectable", listener.local_addr().unwrap());
+        // Accept the connection, then never answer.
+        let server = thread::spawn(move || listener.accept().unwrap());
+        let client = client_with_timeout(Duration::from_millis(200));
+
+        let result = tauri::async_runtime::block_on(fetch_detectable_games(&client, &url, None));
+        match result {
+            Err(e @ SourceError::Network(_)) => assert!(e.is_retryable()),
+            Err(e) => panic!("expected a network error, got {}", e),
+            Ok(_) => panic!("expected a network error"),
+        }
+        server.join().unwrap();
+    }
+}
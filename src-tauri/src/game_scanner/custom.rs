@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tauri::AppHandle;
+
+use super::storage;
+use super::{DetectableGame, GameExecutable};
+
+/// File name of the user's custom games inside the app config directory.
+pub const CUSTOM_GAMES_FILE_NAME: &str = "custom_games.json";
+
+/// Prefix for ids of user-defined games, keeping them apart from Discord's numeric ids.
+pub const CUSTOM_ID_PREFIX: &str = "custom:";
+
+/// Location of the custom games store for `app`, if the config directory is known.
+pub fn store_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_config_dir()
+        .map(|dir| dir.join(CUSTOM_GAMES_FILE_NAME))
+}
+
+/// Reads the custom games from `path`. A missing store yields an empty list,
+/// and an invalid one is set aside and also yields an empty list.
+pub fn load(path: &Path) -> Vec<DetectableGame> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+            storage::set_aside(path, e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn save(path: &Path, games: &[DetectableGame]) -> io::Result<()> {
+    storage::write_json_atomic(path, games)
+}
+
+/// Builds a custom game entry. The id is derived from the name, so adding a
+/// game with the same name again replaces the earlier entry.
+pub fn custom_game(name: &str, executables: &[String]) -> DetectableGame {
+    DetectableGame {
+        id: format!("{}{}", CUSTOM_ID_PREFIX, slug(name)),
+        name: name.trim().to_string(),
+        executables: Some(
+            executables
+                .iter()
+                .map(|exe| GameExecutable {
+                    os: "all".to_string(),
+                    name: exe.trim().to_string(),
+                    ..GameExecutable::default()
+                })
+                .collect(),
+        ),
+        ..DetectableGame::default()
+    }
+}
+
+/// Lowercases `name` and replaces anything but letters and digits with `-`.
+pub fn slug(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// Merges custom games into a fetched list. Custom entries come first so they
+/// win when both lists claim the same executable, and a custom entry reusing a
+/// fetched game's id replaces it outright.
+pub fn merge(custom: &[DetectableGame], fetched: &[DetectableGame]) -> Vec<DetectableGame> {
+    let custom_ids: HashSet<&str> = custom.iter().map(|game| game.id.as_str()).collect();
+
+    custom
+        .iter()
+        .chain(fetched.iter().filter(|game| !custom_ids.contains(game.id.as_str())))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::test_support::temp_dir;
+
+    fn fetched(id: &str, name: &str) -> DetectableGame {
+        DetectableGame {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..DetectableGame::default()
+        }
+    }
+
+    #[test]
+    fn slugs_are_lowercase_with_dashes() {
+        assert_eq!(slug("  Hollow Knight: Silksong "), "hollow-knight--silksong");
+        assert_eq!(slug("DOOM (1993)"), "doom--1993-");
+        assert_eq!(custom_game("Celeste", &[" celeste.exe ".to_string()]).id, "custom:celeste");
+    }
+
+    #[test]
+    fn custom_games_come_first_and_replace_fetched_ids() {
+        let custom = vec![custom_game("Celeste", &["celeste.exe".to_string()]), fetched("2", "Modded Game 2")];
+        let fetched = vec![fetched("1", "Game 1"), fetched("2", "Game 2")];
+
+        let merged = merge(&custom, &fetched);
+        let names: Vec<&str> = merged.iter().map(|game| game.name.as_str()).collect();
+        assert_eq!(names, vec!["Celeste", "Modded Game 2", "Game 1"]);
+    }
+
+    #[test]
+    fn saving_keeps_an_invalid_store() {
+        let dir = temp_dir("custom");
+        let path = dir.join(CUSTOM_GAMES_FILE_NAME);
+        fs::write(&path, b"[{\"id\":").unwrap();
+        assert!(load(&path).is_empty());
+
+        save(&path, &[custom_game("Celeste", &["celeste.exe".to_string()])]).unwrap();
+        assert_eq!(load(&path)[0].id, "custom:celeste");
+        assert_eq!(fs::read(path.with_extension("json.bad")).unwrap(), b"[{\"id\":");
+    }
+}
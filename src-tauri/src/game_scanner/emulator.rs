@@ -0,0 +1,311 @@
+//! Games run in emulators such as RetroArch, Dolphin or PCSX2.
+//!
+//! An emulator on the detectable list is reported under its own name, which
+//! says nothing about what is being played. For the emulators in
+//! `emulators.json`, the content path is picked out of the command line and
+//! its file name cleaned up into a title, with the emulator reported alongside.
+//! Emulators are described by the options they take rather than code, and the
+//! user can add or replace entries with an `emulators.json` of their own in the
+//! app config directory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tauri::AppHandle;
+
+use super::matcher;
+use super::process::ProcessInfo;
+use super::{custom, DetectableGame};
+
+/// Prefix of the ids of games detected through an emulator.
+pub const EMULATOR_ID_PREFIX: &str = "emulator:";
+
+/// File name of the user's emulator definitions inside the app config directory.
+pub const EMULATORS_FILE_NAME: &str = "emulators.json";
+
+const BUNDLED_EMULATORS: &str = include_str!("emulators.json");
+
+/// How to find the content an emulator was started with.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Emulator {
+    pub id: String,
+    pub name: String,
+    /// Executable file names, matched like watch list entries.
+    pub executables: Vec<String>,
+    /// Options followed by the content path, e.g. Dolphin's `--exec`.
+    #[serde(default)]
+    pub content_options: Vec<String>,
+    /// Options followed by a value that isn't the content, e.g. RetroArch's `-L <core>`.
+    #[serde(default)]
+    pub value_options: Vec<String>,
+    /// Extensions of the files the emulator loads. When set, other arguments
+    /// aren't taken for content.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+impl Emulator {
+    /// The content path on `process`'s command line: the value of a content
+    /// option, otherwise the last argument that isn't an option or its value.
+    pub fn content<'a>(&self, process: &'a ProcessInfo) -> Option<&'a str> {
+        let mut args = process.cmd.iter().skip(1).map(String::as_str);
+        let mut options_ended = false;
+        let mut content = None;
+        while let Some(arg) = args.next() {
+            if !options_ended && arg.starts_with('-') {
+                if arg == "--" {
+                    options_ended = true;
+                } else if let Some((option, value)) = arg.split_once('=') {
+                    if self.content_options.iter().any(|o| o == option) {
+                        return Some(value);
+                    }
+                } else if self.content_options.iter().any(|o| o == arg) {
+                    return args.next();
+                } else if self.value_options.iter().any(|o| o == arg) {
+                    args.next();
+                }
+                continue;
+            }
+            if self.loads(arg) {
+                content = Some(arg);
+            }
+        }
+        content
+    }
+
+    fn loads(&self, path: &str) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        let file_name = file_name(path);
+        file_name
+            .rsplit_once('.')
+            .map_or(false, |(_, ext)| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+}
+
+/// The emulators to look for, indexed by executable.
+#[derive(Debug, Default)]
+pub struct Emulators {
+    emulators: Vec<Emulator>,
+    /// Normalized executable file name -> index of the first emulator listing it.
+    index: HashMap<String, usize>,
+}
+
+impl Emulators {
+    pub fn new(emulators: Vec<Emulator>) -> Emulators {
+        let mut index = HashMap::new();
+        for (i, emulator) in emulators.iter().enumerate() {
+            for exe in &emulator.executables {
+                index.entry(matcher::entry_key(exe)).or_insert(i);
+            }
+        }
+        Emulators { emulators, index }
+    }
+
+    /// The emulators shipped with the app, overridden by those in `path`.
+    /// Entries there come first and replace bundled ones with the same id.
+    pub fn load(path: Option<&Path>) -> Emulators {
+        let mut emulators: Vec<Emulator> = path
+            .and_then(|path| {
+                let data = fs::read(path).ok()?;
+                serde_json::from_slice(&data)
+                    .map_err(|e| log::warn!("Ignoring invalid emulators {:?}: {}", path, e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let bundled: Vec<Emulator> = serde_json::from_str(BUNDLED_EMULATORS)
+            .map_err(|e| log::warn!("Ignoring invalid bundled emulators: {}", e))
+            .unwrap_or_default();
+        for emulator in bundled {
+            if !emulators.iter().any(|e| e.id == emulator.id) {
+                emulators.push(emulator);
+            }
+        }
+        Emulators::new(emulators)
+    }
+
+    /// The emulator of the process with [`ProcessInfo::lookup_keys`] `keys`, if any.
+    pub fn emulator(&self, keys: &[String]) -> Option<&Emulator> {
+        keys.iter()
+            .find_map(|key| self.index.get(key))
+            .map(|&i| &self.emulators[i])
+    }
+
+    /// The game `process` is emulating, along with the emulator. `keys` are
+    /// the process's [`ProcessInfo::lookup_keys`].
+    pub fn game(&self, process: &ProcessInfo, keys: &[String]) -> Option<(DetectableGame, &Emulator)> {
+        let emulator = self.emulator(keys)?;
+        let title = rom_title(emulator.content(process)?)?;
+        let game = DetectableGame {
+            id: format!("{}{}:{}", EMULATOR_ID_PREFIX, emulator.id, custom::slug(&title)),
+            name: title,
+            ..DetectableGame::default()
+        };
+        Some((game, emulator))
+    }
+}
+
+/// Location of the user's emulator definitions for `app`, if the config directory is known.
+pub fn emulators_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_config_dir()
+        .map(|dir| dir.join(EMULATORS_FILE_NAME))
+}
+
+/// The last component of `path`, in either separator style, or the file
+/// inside an archive for RetroArch's `archive.zip#file` paths.
+fn file_name(path: &str) -> &str {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    name.rsplit('#').next().unwrap_or(name)
+}
+
+/// A display title for the content at `path`: its file name without the
+/// extension and the `(region)` and `[dump]` tags of ROM set names, with a
+/// trailing ", The" moved to the front.
+pub fn rom_title(path: &str) -> Option<String> {
+    let name = file_name(path);
+    let stem = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()) => stem,
+        _ => name,
+    };
+
+    let mut title = String::new();
+    let mut depth = 0usize;
+    for c in stem.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            '_' if depth == 0 => title.push(' '),
+            c if depth == 0 => title.push(c),
+            _ => {}
+        }
+    }
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let title = title.trim_end_matches(|c: char| c == '-' || c == ',' || c.is_whitespace());
+
+    let (head, rest) = match title.split_once(" - ") {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (title, None),
+    };
+    let head = match head.strip_suffix(", The") {
+        Some(head) => format!("The {}", head),
+        None => head.to_string(),
+    };
+    let title = match rest {
+        Some(rest) => format!("{} - {}", head, rest),
+        None => head,
+    };
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::test_support::temp_dir;
+
+    fn process(name: &str, cmd: &[&str]) -> ProcessInfo {
+        ProcessInfo {
+            name: name.to_string(),
+            cmd: cmd.iter().map(|arg| arg.to_string()).collect(),
+            ..ProcessInfo::default()
+        }
+    }
+
+    fn title(emulators: &Emulators, name: &str, cmd: &[&str]) -> Option<(String, String)> {
+        let process = process(name, cmd);
+        emulators
+            .game(&process, &process.lookup_keys())
+            .map(|(game, emulator)| (game.name, emulator.name.clone()))
+    }
+
+    #[test]
+    fn cleans_up_rom_file_names() {
+        let cases = [
+            ("/roms/snes/Super Mario World (USA).sfc", "Super Mario World"),
+            ("Legend of Zelda, The - A Link to the Past (USA) [!].sfc", "The Legend of Zelda - A Link to the Past"),
+            (r"Z:\games\ps2\Shadow_of_the_Colossus_(Europe).iso", "Shadow of the Colossus"),
+            ("/roms/Final Fantasy VII (USA) (Disc 1).chd", "Final Fantasy VII"),
+            ("/roms/gba.zip#Metroid Fusion (USA).gba", "Metroid Fusion"),
+            ("Dr. Mario", "Dr. Mario"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(rom_title(path).as_deref(), Some(expected), "{}", path);
+        }
+        assert_eq!(rom_title("/roms/(USA).sfc"), None);
+    }
+
+    #[test]
+    fn bundled_emulators_parse() {
+        let bundled: Vec<Emulator> = serde_json::from_str(BUNDLED_EMULATORS).unwrap();
+        assert!(!bundled.is_empty());
+        for emulator in &bundled {
+            assert!(!emulator.executables.is_empty(), "{}", emulator.id);
+            assert_eq!(bundled.iter().filter(|e| e.id == emulator.id).count(), 1, "{}", emulator.id);
+        }
+    }
+
+    #[test]
+    fn finds_content_in_bundled_emulator_command_lines() {
+        let emulators = Emulators::load(None);
+        let retroarch = [
+            "retroarch",
+            "-L",
+            "/usr/lib/libretro/snes9x_libretro.so",
+            "--config",
+            "/home/user/retroarch.cfg",
+            "-f",
+            "/roms/Super Metroid (Japan, USA) (En,Ja).sfc",
+        ];
+        assert_eq!(
+            title(&emulators, "retroarch", &retroarch),
+            Some(("Super Metroid".to_string(), "RetroArch".to_string()))
+        );
+
+        let dolphin = ["dolphin-emu", "-b", "--exec=/games/Metroid Prime (USA).rvz"];
+        assert_eq!(title(&emulators, "dolphin-emu", &dolphin).unwrap().0, "Metroid Prime");
+
+        let pcsx2 = ["pcsx2-qt", "-fullscreen", "-state", "1", "--", "/games/Okami (USA).iso"];
+        assert_eq!(title(&emulators, "pcsx2-qt", &pcsx2).unwrap().0, "Okami");
+
+        // Without content the emulator is left to the watch list.
+        assert_eq!(title(&emulators, "retroarch", &["retroarch", "-L", "/cores/x.so"]), None);
+        assert_eq!(title(&emulators, "pcsx2-qt", &["pcsx2-qt", "-bigpicture", "settings.ini"]), None);
+        assert_eq!(title(&emulators, "hades", &["hades", "/games/Hades (USA).iso"]), None);
+    }
+
+    #[test]
+    fn user_definitions_come_first() {
+        let dir = temp_dir("emulators");
+        let path = dir.join(EMULATORS_FILE_NAME);
+        fs::write(
+            &path,
+            br#"[
+                {"id": "retroarch", "name": "RetroArch (custom)", "executables": ["retroarch"], "extensions": ["sfc"]},
+                {"id": "xemu", "name": "xemu", "executables": ["xemu"], "content_options": ["-dvd_path"]}
+            ]"#,
+        )
+        .unwrap();
+        let emulators = Emulators::load(Some(&path));
+
+        let retroarch = process("retroarch", &["retroarch", "/roms/Contra.nes", "/roms/F-Zero.sfc"]);
+        let (game, emulator) = emulators.game(&retroarch, &retroarch.lookup_keys()).unwrap();
+        assert_eq!((game.id.as_str(), game.name.as_str()), ("emulator:retroarch:f-zero", "F-Zero"));
+        assert_eq!(emulator.name, "RetroArch (custom)");
+        assert_eq!(
+            title(&emulators, "xemu", &["xemu", "-dvd_path", "/games/Halo.iso"]).unwrap().0,
+            "Halo"
+        );
+        assert!(emulators.emulator(&process("dolphin-emu", &[]).lookup_keys()).is_some());
+
+        fs::write(&path, b"not json").unwrap();
+        let retroarch = process("retroarch", &[]).lookup_keys();
+        assert_eq!(Emulators::load(Some(&path)).emulator(&retroarch).unwrap().name, "RetroArch");
+    }
+}
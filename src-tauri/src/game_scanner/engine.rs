@@ -0,0 +1,527 @@
+//! The scanner's detection logic as pure functions over process snapshots.
+//!
+//! The scan loop feeds a snapshot from a [`ProcessSource`](super::process::ProcessSource)
+//! into a [`Detector`] and turns the returned [`ScanEvent`]s into frontend
+//! events, so everything between "these processes are running" and "this game
+//! started" can be tested without real processes or a Tauri app.
+
+use super::activity::{ActivitySet, Detection, LauncherPolicy, PrimaryPolicy, RunningGame};
+use super::hysteresis::{Debouncer, Hysteresis};
+use super::index::WatchList;
+use super::matcher::MatchOptions;
+use super::privacy::IgnoreList;
+use super::process::ProcessInfo;
+use super::steam::{self, SteamGames};
+
+/// Settings that affect detection, captured once per scan.
+#[derive(Clone, Debug, Default)]
+pub struct ScanConfig {
+    pub options: MatchOptions,
+    pub hysteresis: Hysteresis,
+    pub policy: PrimaryPolicy,
+    pub pinned: Option<String>,
+    pub ignored: IgnoreList,
+    pub launchers: LauncherPolicy,
+}
+
+/// A change produced by a scan.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanEvent {
+    Started(RunningGame),
+    Stopped { game: RunningGame, ended_at: u64 },
+    /// A running game was added to the ignore list.
+    Hidden(RunningGame),
+    /// A running game was taken off the ignore list.
+    Shown(RunningGame),
+    /// The primary activity moved to another game, or to none.
+    PrimaryChanged {
+        previous: Option<RunningGame>,
+        current: Option<RunningGame>,
+    },
+}
+
+/// Matches a snapshot against the watch list, one detection per game.
+/// Emulators running known content are reported as that content, and
+/// processes launched through Steam that match nothing else are looked up in `steam`.
+pub fn detect(watch_list: &WatchList, steam: &SteamGames, snapshot: &[ProcessInfo], config: &ScanConfig) -> Vec<Detection> {
+    let mut detected: Vec<Detection> = Vec::new();
+    for process in snapshot {
+        let keys = process.lookup_keys();
+        let emulated = watch_list.emulated_game(process, &keys);
+        let found = match &emulated {
+            Some((game, _)) => Some((game, None)),
+            None => watch_list
+                .lookup(process, &keys, config.options)
+                .map(|(game, exe)| (game, Some(exe)))
+                .or_else(|| Some((steam.get(steam::app_id(process)?)?, None))),
+        };
+        let (game, exe) = match found {
+            Some(found) => found,
+            None => continue,
+        };
+        let is_launcher = exe.map_or(false, |exe| exe.is_launcher);
+        if is_launcher && config.launchers == LauncherPolicy::Exclude {
+            continue;
+        }
+
+        // A game with several matching processes started with its earliest one
+        if let Some(detection) = detected.iter_mut().find(|detection| detection.id == game.id) {
+            detection.started_at = match (detection.started_at, process.start_time) {
+                (Some(earlier), Some(started_at)) => Some(earlier.min(started_at)),
+                (earlier, started_at) => earlier.or(started_at),
+            };
+            // Report the game's own executable over its launcher.
+            if let Some(exe) = exe {
+                if (detection.is_launcher && !is_launcher) || detection.executable_name.is_none() {
+                    detection.is_launcher = is_launcher;
+                    detection.executable_name = Some(exe.name.clone());
+                }
+            }
+            continue;
+        }
+        let emulator = emulated.as_ref().map(|(_, emulator)| emulator.name.clone());
+        log::debug!("Matched process '{}' (pid {}) to '{}'", process.name, process.pid, game.name);
+        detected.push(Detection {
+            id: game.id.clone(),
+            name: game.name.clone(),
+            executable_name: exe.map(|exe| exe.name.clone()).or_else(|| emulator.as_ref().map(|_| process.name.clone())),
+            started_at: process.start_time,
+            hidden: config.ignored.hides(game),
+            is_launcher,
+            aliases: game.aliases.clone(),
+            icon_hash: game.icon_hash.clone(),
+            emulator,
+        });
+    }
+
+    // A Steam game missing from the list can still match another entry with
+    // its own executable, e.g. a custom game; report it once.
+    let listed: Vec<String> = detected
+        .iter()
+        .filter(|detection| !detection.id.starts_with(steam::STEAM_ID_PREFIX))
+        .map(|detection| detection.name.to_lowercase())
+        .collect();
+    detected.retain(|detection| {
+        !detection.id.starts_with(steam::STEAM_ID_PREFIX) || !listed.contains(&detection.name.to_lowercase())
+    });
+    detected
+}
+
+/// Tracks running games across scans and reports what changed.
+#[derive(Default)]
+pub struct Detector {
+    debouncer: Debouncer,
+    activities: ActivitySet,
+    primary: Option<RunningGame>,
+}
+
+impl Detector {
+    /// Processes one snapshot taken at `now`.
+    pub fn scan(
+        &mut self,
+        watch_list: &WatchList,
+        steam: &SteamGames,
+        snapshot: &[ProcessInfo],
+        now: u64,
+        config: &ScanConfig,
+    ) -> Vec<ScanEvent> {
+        let detected = detect(watch_list, steam, snapshot, config);
+        let running = self.debouncer.update(detected, now, config.hysteresis);
+        let changes = self.activities.update(running, now);
+
+        let mut events: Vec<ScanEvent> = changes
+            .stopped
+            .into_iter()
+            .map(|game| ScanEvent::Stopped { game, ended_at: now })
+            .chain(changes.started.into_iter().map(ScanEvent::Started))
+            .chain(changes.hidden.into_iter().map(ScanEvent::Hidden))
+            .chain(changes.shown.into_iter().map(ScanEvent::Shown))
+            .collect();
+
+        let primary = self
+            .activities
+            .primary(config.policy, config.pinned.as_deref())
+            .cloned();
+        if primary.as_ref().map(|game| &game.id) != self.primary.as_ref().map(|game| &game.id) {
+            events.push(ScanEvent::PrimaryChanged {
+                previous: self.primary.take(),
+                current: primary.clone(),
+            });
+        }
+        self.primary = primary;
+
+        events
+    }
+
+    /// Ends every running game at `now`, as if all of them had exited, and
+    /// forgets any game still waiting to be confirmed.
+    pub fn stop_all(&mut self, now: u64) -> Vec<ScanEvent> {
+        let mut events: Vec<ScanEvent> = self
+            .activities
+            .games()
+            .cloned()
+            .map(|game| ScanEvent::Stopped { game, ended_at: now })
+            .collect();
+        if let Some(previous) = self.primary.take() {
+            events.push(ScanEvent::PrimaryChanged {
+                previous: Some(previous),
+                current: None,
+            });
+        }
+        *self = Detector::default();
+        events
+    }
+
+    pub fn activities(&self) -> &ActivitySet {
+        &self.activities
+    }
+
+    /// The primary activity as of the last scan.
+    pub fn primary(&self) -> Option<&RunningGame> {
+        self.primary.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::game_scanner::emulator::{Emulator, Emulators};
+    use crate::game_scanner::matcher::CURRENT_OS;
+    use crate::game_scanner::process::{ProcessSource, ScriptedSource};
+    use crate::game_scanner::test_support::temp_dir;
+    use crate::game_scanner::{DetectableGame, GameExecutable};
+
+    fn watch_list() -> WatchList {
+        let game = |id: &str, exe: &str| DetectableGame {
+            id: id.to_string(),
+            name: format!("Game {}", id),
+            executables: Some(vec![GameExecutable {
+                os: CURRENT_OS.to_string(),
+                name: exe.to_string(),
+                ..GameExecutable::default()
+            }]),
+            ..DetectableGame::default()
+        };
+        WatchList::new(vec![game("a", "alpha"), game("b", "beta")])
+    }
+
+    fn process(pid: u32, name: &str, start_time: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            start_time: Some(start_time),
+            ..ProcessInfo::default()
+        }
+    }
+
+    /// Runs every scripted snapshot through a detector, one scan per 10 seconds.
+    fn run(config: &ScanConfig, snapshots: Vec<Vec<ProcessInfo>>) -> Vec<Vec<ScanEvent>> {
+        let watch_list = watch_list();
+        let scans = snapshots.len();
+        let mut source = ScriptedSource::new(snapshots);
+        let mut detector = Detector::default();
+        (0..scans)
+            .map(|i| detector.scan(&watch_list, &SteamGames::default(), &source.snapshot(), 100 + 10 * i as u64, config))
+            .collect()
+    }
+
+    fn running(id: &str, started_at: u64) -> RunningGame {
+        RunningGame {
+            id: id.to_string(),
+            name: format!("Game {}", id),
+            executable_name: Some(if id == "a" { "alpha" } else { "beta" }.to_string()),
+            started_at,
+            ..RunningGame::default()
+        }
+    }
+
+    #[test]
+    fn detect_merges_processes_of_the_same_game() {
+        let snapshot = vec![process(1, "alpha", 50), process(2, "unrelated", 10), process(3, "alpha", 40)];
+
+        let detected = detect(&watch_list(), &SteamGames::default(), &snapshot, &ScanConfig::default());
+        assert_eq!(detected.len(), 1);
+        assert_eq!(detected[0].id, "a");
+        assert_eq!(detected[0].started_at, Some(40));
+    }
+
+    #[test]
+    fn reports_starts_stops_and_primary_changes() {
+        let config = ScanConfig {
+            hysteresis: Hysteresis {
+                start_scans: 1,
+                stop_scans: 1,
+            },
+            ..ScanConfig::default()
+        };
+        let a = running("a", 90);
+        let b = running("b", 95);
+
+        let events = run(
+            &config,
+            vec![
+                vec![process(1, "alpha", 90)],
+                vec![process(1, "alpha", 90), process(2, "beta", 95)],
+                vec![process(1, "alpha", 90), process(2, "beta", 95)],
+                vec![process(1, "alpha", 90)],
+                vec![],
+            ],
+        );
+
+        assert_eq!(
+            events,
+            vec![
+                vec![
+                    ScanEvent::Started(a.clone()),
+                    ScanEvent::PrimaryChanged {
+                        previous: None,
+                        current: Some(a.clone()),
+                    },
+                ],
+                vec![
+                    ScanEvent::Started(b.clone()),
+                    ScanEvent::PrimaryChanged {
+                        previous: Some(a.clone()),
+                        current: Some(b.clone()),
+                    },
+                ],
+                vec![],
+                vec![
+                    ScanEvent::Stopped {
+                        game: b.clone(),
+                        ended_at: 130,
+                    },
+                    ScanEvent::PrimaryChanged {
+                        previous: Some(b),
+                        current: Some(a.clone()),
+                    },
+                ],
+                vec![
+                    ScanEvent::Stopped {
+                        game: a.clone(),
+                        ended_at: 140,
+                    },
+                    ScanEvent::PrimaryChanged {
+                        previous: Some(a),
+                        current: None,
+                    },
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn a_briefly_missing_game_keeps_running() {
+        let config = ScanConfig {
+            hysteresis: Hysteresis {
+                start_scans: 1,
+                stop_scans: 2,
+            },
+            policy: PrimaryPolicy::LongestRunning,
+            ..ScanConfig::default()
+        };
+
+        let events = run(
+            &config,
+            vec![
+                vec![process(1, "alpha", 90)],
+                vec![],
+                vec![process(7, "alpha", 118)],
+                vec![],
+                vec![],
+            ],
+        );
+
+        let counts: Vec<usize> = events.iter().map(Vec::len).collect();
+        assert_eq!(counts, vec![2, 0, 0, 0, 2]);
+        assert_eq!(
+            events[4][0],
+            ScanEvent::Stopped {
+                game: running("a", 90),
+                ended_at: 140,
+            }
+        );
+    }
+
+    #[test]
+    fn ignored_games_are_tracked_but_not_primary() {
+        let mut config = ScanConfig::default();
+        config.ignored.games.insert("b".to_string());
+        let watch_list = watch_list();
+        let mut detector = Detector::default();
+
+        let events = detector.scan(&watch_list, &SteamGames::default(), &[process(1, "alpha", 90), process(2, "beta", 95)], 100, &config);
+        let hidden_b = RunningGame {
+            hidden: true,
+            ..running("b", 95)
+        };
+        assert!(events.contains(&ScanEvent::Started(hidden_b.clone())));
+        assert_eq!(detector.primary().map(|game| game.id.as_str()), Some("a"));
+
+        config.ignored.games.clear();
+        let events = detector.scan(&watch_list, &SteamGames::default(), &[process(1, "alpha", 90), process(2, "beta", 95)], 110, &config);
+        assert_eq!(events[0], ScanEvent::Shown(running("b", 95)));
+        assert_eq!(detector.primary().map(|game| game.id.as_str()), Some("b"));
+    }
+
+    #[test]
+    fn stop_all_ends_every_running_game() {
+        let watch_list = watch_list();
+        let mut detector = Detector::default();
+        let config = ScanConfig {
+            hysteresis: Hysteresis {
+                start_scans: 1,
+                stop_scans: 3,
+            },
+            ..ScanConfig::default()
+        };
+        detector.scan(&watch_list, &SteamGames::default(), &[process(1, "alpha", 90), process(2, "beta", 95)], 100, &config);
+
+        assert_eq!(
+            detector.stop_all(120),
+            vec![
+                ScanEvent::Stopped {
+                    game: running("a", 90),
+                    ended_at: 120,
+                },
+                ScanEvent::Stopped {
+                    game: running("b", 95),
+                    ended_at: 120,
+                },
+                ScanEvent::PrimaryChanged {
+                    previous: Some(running("b", 95)),
+                    current: None,
+                },
+            ]
+        );
+        assert!(detector.primary().is_none());
+        assert_eq!(detector.stop_all(130), Vec::new());
+
+        // Nothing of the earlier sightings carries over.
+        let events = detector.scan(&watch_list, &SteamGames::default(), &[process(1, "alpha", 90)], 140, &config);
+        assert_eq!(events[0], ScanEvent::Started(running("a", 90)));
+    }
+
+    #[test]
+    fn sessions_stopped_mid_game_end_at_the_stop_time() {
+        use crate::game_scanner::history;
+
+        let dir = temp_dir("stop");
+        let path = dir.join(history::HISTORY_FILE_NAME);
+        let watch_list = watch_list();
+        let mut detector = Detector::default();
+        let config = ScanConfig::default();
+        detector.scan(&watch_list, &SteamGames::default(), &[process(1, "alpha", 90)], 100, &config);
+
+        // Disabling the scanner at 150 ends the session there, however long
+        // it stays disabled.
+        assert!(history::record(&path, &detector.stop_all(150)).unwrap().is_ok());
+        detector.scan(&watch_list, &SteamGames::default(), &[], 9_000, &config);
+        assert!(history::record(&path, &detector.stop_all(9_000)).is_none());
+
+        let sessions = history::load(&path);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].started_at, sessions[0].ended_at, sessions[0].duration), (90, 150, 60));
+    }
+
+    #[test]
+    fn launchers_can_be_excluded() {
+        let launcher = DetectableGame {
+            id: "l".to_string(),
+            name: "Launcher".to_string(),
+            executables: Some(vec![
+                GameExecutable {
+                    os: CURRENT_OS.to_string(),
+                    name: "launcher".to_string(),
+                    is_launcher: true,
+                    ..GameExecutable::default()
+                },
+                GameExecutable {
+                    os: CURRENT_OS.to_string(),
+                    name: "game".to_string(),
+                    ..GameExecutable::default()
+                },
+            ]),
+            ..DetectableGame::default()
+        };
+        let watch_list = WatchList::new(vec![launcher]);
+        let snapshot = vec![process(1, "launcher", 10)];
+
+        let detected = detect(&watch_list, &SteamGames::default(), &snapshot, &ScanConfig::default());
+        assert!(detected[0].is_launcher);
+        let exclude = ScanConfig {
+            launchers: LauncherPolicy::Exclude,
+            ..ScanConfig::default()
+        };
+        assert!(detect(&watch_list, &SteamGames::default(), &snapshot, &exclude).is_empty());
+
+        // Once the game itself runs, the detection is no longer a launcher.
+        let snapshot = vec![process(1, "launcher", 10), process(2, "game", 20)];
+        let detected = detect(&watch_list, &SteamGames::default(), &snapshot, &ScanConfig::default());
+        assert!(!detected[0].is_launcher);
+        assert_eq!(detected[0].executable_name.as_deref(), Some("game"));
+        assert_eq!(detect(&watch_list, &SteamGames::default(), &snapshot, &exclude)[0].started_at, Some(20));
+    }
+
+    #[test]
+    fn steam_launches_are_detected_by_app_id() {
+        let reaper = |pid, app_id: &str| ProcessInfo {
+            pid,
+            name: "reaper".to_string(),
+            cmd: vec!["reaper".to_string(), "SteamLaunch".to_string(), format!("AppId={}", app_id)],
+            start_time: Some(10),
+            ..ProcessInfo::default()
+        };
+        let steam_game = |id: &str, name: &str| DetectableGame {
+            id: id.to_string(),
+            name: name.to_string(),
+            ..DetectableGame::default()
+        };
+        let mut steam = SteamGames::default();
+        steam.insert(620, steam_game("steam:620", "Portal 2"));
+        steam.insert(700, steam_game("steam:700", "Game a"));
+        steam.insert(800, steam_game("b", "Game b"));
+
+        let snapshot = vec![reaper(1, "620"), reaper(2, "700"), process(3, "alpha", 20), reaper(4, "800"), process(5, "beta", 30)];
+        let detected = detect(&watch_list(), &steam, &snapshot, &ScanConfig::default());
+
+        let found: Vec<_> = detected
+            .iter()
+            .map(|detection| (detection.id.as_str(), detection.executable_name.as_deref(), detection.started_at))
+            .collect();
+        // Steam's "Game a" is already found through its executable, and Steam's
+        // listing of "b" merges with the process of the same game.
+        assert_eq!(found, vec![("steam:620", None, Some(10)), ("a", Some("alpha"), Some(20)), ("b", Some("beta"), Some(10))]);
+    }
+
+    #[test]
+    fn emulators_report_their_content() {
+        let emulator = Emulator {
+            id: "alpha-emu".to_string(),
+            name: "Alpha Emulator".to_string(),
+            executables: vec!["alpha".to_string()],
+            content_options: Vec::new(),
+            value_options: vec!["--core".to_string()],
+            extensions: Vec::new(),
+        };
+        let watch_list = watch_list().with_emulators(Arc::new(Emulators::new(vec![emulator])));
+        let alpha = |pid, args: &[&str]| ProcessInfo {
+            cmd: ["alpha"].iter().chain(args).map(|arg| arg.to_string()).collect(),
+            ..process(pid, "alpha", 10)
+        };
+
+        let detected = detect(&watch_list, &SteamGames::default(), &[alpha(1, &["--core", "x.so", "/roms/Kirby's Adventure (USA).nes"])], &ScanConfig::default());
+        let found: Vec<_> = detected
+            .iter()
+            .map(|detection| (detection.id.as_str(), detection.name.as_str(), detection.emulator.as_deref()))
+            .collect();
+        assert_eq!(found, vec![("emulator:alpha-emu:kirby-s-adventure", "Kirby's Adventure", Some("Alpha Emulator"))]);
+        assert_eq!(detected[0].executable_name.as_deref(), Some("alpha"));
+
+        // Without content the emulator is matched as itself.
+        let detected = detect(&watch_list, &SteamGames::default(), &[alpha(1, &["--core", "x.so"])], &ScanConfig::default());
+        assert_eq!((detected[0].id.as_str(), detected[0].emulator.as_deref()), ("a", None));
+    }
+}
@@ -0,0 +1,6 @@
+-- The games table of a Lutris pga.db, with the columns Lutris creates.
+-- Celeste is left out to cover naming a game from its slug.
+CREATE TABLE games (id INTEGER PRIMARY KEY, name TEXT, sortname TEXT, slug TEXT, installer_slug TEXT, parent_slug TEXT, platform TEXT, runner TEXT, executable TEXT, directory TEXT, updated DATETIME, lastplayed INTEGER, installed INTEGER, installed_at INTEGER, year INTEGER, configpath TEXT, has_custom_banner INTEGER, has_custom_icon INTEGER, has_custom_coverart_big INTEGER, playtime REAL, hidden INTEGER, service TEXT, service_id TEXT, discord_id TEXT);
+INSERT INTO games VALUES(1, 'Hollow Knight', NULL, 'hollow-knight', NULL, NULL, 'Windows', 'wine', NULL, NULL, NULL, NULL, 1, 1612345678, 2017, 'hollow-knight-1612345678', NULL, NULL, NULL, 12.5, 0, NULL, NULL, NULL);
+INSERT INTO games VALUES(2, 'The Witcher 3: Wild Hunt', NULL, 'the-witcher-3-wild-hunt', NULL, NULL, 'Windows', 'wine', NULL, NULL, NULL, NULL, 1, 1612345678, 2015, 'the-witcher-3-wild-hunt-1612345681', NULL, NULL, NULL, 80.25, 0, NULL, NULL, NULL);
+INSERT INTO games VALUES(3, 'Snes9x', NULL, 'retroarch-snes', NULL, NULL, 'Windows', 'libretro', NULL, NULL, NULL, NULL, 1, 1612345678, NULL, 'retroarch-snes-1612345680', NULL, NULL, NULL, 0.0, 0, NULL, NULL, NULL);
@@ -0,0 +1,266 @@
+//! Local playtime history.
+//!
+//! Finished sessions are appended to a JSON Lines file in the app data
+//! directory, one session per line, so recording never rewrites earlier data
+//! and a torn final line only loses that one session.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::activity::RunningGame;
+use super::engine::ScanEvent;
+
+/// File name of the playtime history inside the app data directory.
+pub const HISTORY_FILE_NAME: &str = "playtime_history.jsonl";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A single finished play session.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Session {
+    pub game_id: String,
+    pub name: String,
+    pub executable_name: Option<String>,
+    /// Unix timestamps (seconds).
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// Length of the session in seconds.
+    pub duration: u64,
+}
+
+impl Session {
+    pub fn new(game: &RunningGame, ended_at: u64) -> Session {
+        Session {
+            game_id: game.id.clone(),
+            name: game.name.clone(),
+            executable_name: game.executable_name.clone(),
+            started_at: game.started_at,
+            ended_at,
+            duration: ended_at.saturating_sub(game.started_at),
+        }
+    }
+}
+
+/// Total playtime of one game.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GameTotal {
+    pub game_id: String,
+    pub name: String,
+    pub total_seconds: u64,
+    pub sessions: usize,
+    pub last_played: u64,
+}
+
+/// Playtime of one game on one calendar day.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DayTotal {
+    /// The day as `YYYY-MM-DD`.
+    pub date: String,
+    pub game_id: String,
+    pub name: String,
+    pub seconds: u64,
+}
+
+/// Location of the history file for `app`, if the data directory is known.
+pub fn history_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_data_dir()
+        .map(|dir| dir.join(HISTORY_FILE_NAME))
+}
+
+/// Appends a finished session to the history at `path`.
+pub fn append(path: &Path, session: &Session) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_vec(session)?;
+    line.push(b'\n');
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(&line)
+}
+
+/// Appends the sessions finished by `events` to the history at `path`.
+/// Returns the outcome of writing them, or `None` if no session finished.
+pub fn record(path: &Path, events: &[ScanEvent]) -> Option<io::Result<()>> {
+    let mut recorded: Option<io::Result<()>> = None;
+    for event in events {
+        if let ScanEvent::Stopped { game, ended_at } = event {
+            let result = append(path, &Session::new(game, *ended_at));
+            recorded = Some(recorded.unwrap_or(Ok(())).and(result));
+        }
+    }
+    recorded
+}
+
+/// Reads every session recorded at `path`, skipping lines that don't parse.
+pub fn load(path: &Path) -> Vec<Session> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(_) => return Vec::new(),
+    };
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Total playtime per game, most played first.
+pub fn totals_per_game(sessions: &[Session]) -> Vec<GameTotal> {
+    let mut totals: BTreeMap<&str, GameTotal> = BTreeMap::new();
+    for session in sessions {
+        let total = totals.entry(&session.game_id).or_insert_with(|| GameTotal {
+            game_id: session.game_id.clone(),
+            name: session.name.clone(),
+            total_seconds: 0,
+            sessions: 0,
+            last_played: 0,
+        });
+        total.total_seconds += session.duration;
+        total.sessions += 1;
+        if session.ended_at >= total.last_played {
+            // Keep the most recent name in case the game was renamed upstream.
+            total.name = session.name.clone();
+            total.last_played = session.ended_at;
+        }
+    }
+
+    let mut totals: Vec<GameTotal> = totals.into_values().collect();
+    totals.sort_by_key(|t| std::cmp::Reverse(t.total_seconds));
+    totals
+}
+
+/// Playtime per game per calendar day, oldest day first. Days are computed in
+/// the time zone `utc_offset_minutes` ahead of UTC, and sessions spanning
+/// midnight are split between the days they cover. Only playtime at or after
+/// `since` is counted.
+pub fn totals_per_day(sessions: &[Session], since: u64, utc_offset_minutes: i32) -> Vec<DayTotal> {
+    let offset = i64::from(utc_offset_minutes) * 60;
+    let mut totals: BTreeMap<(i64, &str), DayTotal> = BTreeMap::new();
+
+    for session in sessions {
+        let mut start = session.started_at.max(since) as i64 + offset;
+        let end = session.ended_at as i64 + offset;
+        while start < end {
+            let day = start.div_euclid(SECONDS_PER_DAY);
+            let day_end = ((day + 1) * SECONDS_PER_DAY).min(end);
+            let total = totals.entry((day, &session.game_id)).or_insert_with(|| DayTotal {
+                date: format_date(day),
+                game_id: session.game_id.clone(),
+                name: session.name.clone(),
+                seconds: 0,
+            });
+            total.seconds += (day_end - start) as u64;
+            start = day_end;
+        }
+    }
+
+    totals.into_values().collect()
+}
+
+/// The `limit` most recently finished sessions, newest first.
+pub fn recent_sessions(sessions: &[Session], limit: usize) -> Vec<Session> {
+    let mut recent = sessions.to_vec();
+    recent.sort_by_key(|s| std::cmp::Reverse(s.ended_at));
+    recent.truncate(limit);
+    recent
+}
+
+/// Formats a count of days since the Unix epoch as `YYYY-MM-DD`.
+fn format_date(days: i64) -> String {
+    // Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::test_support::temp_dir;
+
+    // 2024-03-09T22:00:00Z
+    const SATURDAY_10PM: u64 = 1_710_021_600;
+    const HOUR: u64 = 60 * 60;
+
+    fn session(game_id: &str, started_at: u64, duration: u64) -> Session {
+        Session {
+            game_id: game_id.to_string(),
+            name: format!("Game {}", game_id),
+            executable_name: None,
+            started_at,
+            ended_at: started_at + duration,
+            duration,
+        }
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(-1), "1969-12-31");
+        assert_eq!(format_date(19_782), "2024-02-29");
+        assert_eq!(format_date((SATURDAY_10PM / 86_400) as i64), "2024-03-09");
+    }
+
+    #[test]
+    fn torn_lines_do_not_hide_other_sessions() {
+        let dir = temp_dir("history");
+        let path = dir.join(HISTORY_FILE_NAME);
+
+        append(&path, &session("a", 100, 50)).unwrap();
+        append(&path, &session("b", 200, 10)).unwrap();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"game_id\":").unwrap();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"\n").unwrap();
+        append(&path, &session("c", 300, 5)).unwrap();
+
+        let ids: Vec<_> = load(&path).into_iter().map(|session| session.game_id).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn totals_are_summed_per_game() {
+        let sessions = vec![session("a", 0, 10), session("b", 20, 100), session("a", 200, 30)];
+
+        let totals = totals_per_game(&sessions);
+        assert_eq!(totals.len(), 2);
+        assert_eq!((totals[0].game_id.as_str(), totals[0].total_seconds, totals[0].sessions), ("b", 100, 1));
+        assert_eq!((totals[1].game_id.as_str(), totals[1].total_seconds, totals[1].sessions), ("a", 40, 2));
+        assert_eq!(totals[1].last_played, 230);
+    }
+
+    #[test]
+    fn sessions_are_split_across_days() {
+        let sessions = vec![session("a", SATURDAY_10PM, 3 * HOUR)];
+
+        let utc = totals_per_day(&sessions, 0, 0);
+        let utc: Vec<_> = utc.iter().map(|day| (day.date.as_str(), day.seconds)).collect();
+        assert_eq!(utc, vec![("2024-03-09", 2 * HOUR), ("2024-03-10", HOUR)]);
+
+        // UTC-05:00: the whole session falls on the Saturday evening.
+        let eastern = totals_per_day(&sessions, 0, -300);
+        let eastern: Vec<_> = eastern.iter().map(|day| (day.date.as_str(), day.seconds)).collect();
+        assert_eq!(eastern, vec![("2024-03-09", 3 * HOUR)]);
+
+        let since_midnight = totals_per_day(&sessions, SATURDAY_10PM + 2 * HOUR, 0);
+        assert_eq!(since_midnight.len(), 1);
+        assert_eq!(since_midnight[0].seconds, HOUR);
+    }
+
+    #[test]
+    fn recent_sessions_are_newest_first() {
+        let sessions = vec![session("a", 0, 10), session("b", 100, 10), session("c", 50, 10)];
+
+        let recent: Vec<_> = recent_sessions(&sessions, 2).into_iter().map(|session| session.game_id).collect();
+        assert_eq!(recent, vec!["b", "c"]);
+    }
+}
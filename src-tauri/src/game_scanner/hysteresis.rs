@@ -0,0 +1,187 @@
+//! Debouncing of game start/stop transitions.
+//!
+//! Launchers, updaters and crash-restarts make a game vanish for a scan or two,
+//! which would otherwise show up as a stop followed by a start. The
+//! [`Debouncer`] sits between the raw per-scan detections and the activity set:
+//! a game has to be seen for `start_scans` consecutive scans before it counts
+//! as running, and missing for `stop_scans` consecutive scans before it stops.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::activity::Detection;
+
+/// How many consecutive scans confirm a start or a stop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Hysteresis {
+    pub start_scans: u32,
+    pub stop_scans: u32,
+}
+
+impl Default for Hysteresis {
+    fn default() -> Self {
+        // Report starts straight away, but ride out a single missed scan.
+        Hysteresis {
+            start_scans: 1,
+            stop_scans: 2,
+        }
+    }
+}
+
+struct Candidate {
+    detection: Detection,
+    seen: u32,
+}
+
+struct Confirmed {
+    detection: Detection,
+    missed: u32,
+}
+
+/// Turns raw per-scan detections into a stable set of running games.
+#[derive(Default)]
+pub struct Debouncer {
+    candidates: BTreeMap<String, Candidate>,
+    confirmed: BTreeMap<String, Confirmed>,
+}
+
+impl Debouncer {
+    /// Feeds the detections of one scan at `now` and returns the games that
+    /// should currently be considered running.
+    pub fn update(&mut self, detected: Vec<Detection>, now: u64, config: Hysteresis) -> Vec<Detection> {
+        let mut detected: BTreeMap<String, Detection> = detected
+            .into_iter()
+            .map(|detection| (detection.id.clone(), detection))
+            .collect();
+
+        // Confirmed games: refresh the ones still present, count misses for the rest.
+        self.confirmed.retain(|id, confirmed| match detected.remove(id) {
+            Some(mut detection) => {
+                detection.started_at = confirmed.detection.started_at.or(detection.started_at);
+                confirmed.detection = detection;
+                confirmed.missed = 0;
+                true
+            }
+            None => {
+                confirmed.missed += 1;
+                confirmed.missed < config.stop_scans.max(1)
+            }
+        });
+
+        // Candidates must be seen on consecutive scans; a gap starts them over.
+        self.candidates.retain(|id, _| detected.contains_key(id));
+        for (id, detection) in detected {
+            let candidate = self.candidates.entry(id.clone()).or_insert_with(|| Candidate {
+                detection: Detection {
+                    // Without a process start time, the game started when first seen.
+                    started_at: detection.started_at.or(Some(now)),
+                    ..detection.clone()
+                },
+                seen: 0,
+            });
+            candidate.seen += 1;
+            candidate.detection.executable_name = detection.executable_name;
+            candidate.detection.hidden = detection.hidden;
+            candidate.detection.is_launcher = detection.is_launcher;
+
+            if candidate.seen >= config.start_scans.max(1) {
+                let candidate = self.candidates.remove(&id).unwrap();
+                self.confirmed.insert(
+                    id,
+                    Confirmed {
+                        detection: candidate.detection,
+                        missed: 0,
+                    },
+                );
+            }
+        }
+
+        self.confirmed
+            .values()
+            .map(|confirmed| confirmed.detection.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(id: &str) -> Detection {
+        Detection {
+            id: id.to_string(),
+            name: format!("Game {}", id),
+            ..Detection::default()
+        }
+    }
+
+    /// Runs `scans` through a debouncer, returning the running ids after each scan.
+    fn run(config: Hysteresis, scans: &[&[&str]]) -> Vec<Vec<String>> {
+        let mut debouncer = Debouncer::default();
+        scans
+            .iter()
+            .enumerate()
+            .map(|(i, ids)| {
+                let detected = ids.iter().map(|id| detection(id)).collect();
+                debouncer
+                    .update(detected, i as u64, config)
+                    .into_iter()
+                    .map(|detection| detection.id)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn config(start_scans: u32, stop_scans: u32) -> Hysteresis {
+        Hysteresis {
+            start_scans,
+            stop_scans,
+        }
+    }
+
+    #[test]
+    fn without_hysteresis_detections_pass_through() {
+        assert_eq!(
+            run(config(1, 1), &[&["a"], &[], &["a", "b"]]),
+            vec![vec!["a"], vec![], vec!["a", "b"]]
+        );
+    }
+
+    #[test]
+    fn starts_need_consecutive_sightings() {
+        assert_eq!(
+            run(config(3, 1), &[&["a"], &["a"], &[], &["a"], &["a"], &["a"]]),
+            vec![vec![], vec![], vec![], vec![], vec![], vec!["a"]]
+        );
+    }
+
+    #[test]
+    fn brief_gaps_do_not_stop_a_game() {
+        assert_eq!(
+            run(config(1, 3), &[&["a"], &[], &[], &["a"], &[], &[], &[]]),
+            vec![vec!["a"], vec!["a"], vec!["a"], vec!["a"], vec!["a"], vec!["a"], vec![]]
+        );
+    }
+
+    #[test]
+    fn zero_thresholds_behave_like_one() {
+        assert_eq!(run(config(0, 0), &[&["a"], &[]]), vec![vec!["a"], vec![]]);
+    }
+
+    #[test]
+    fn start_time_defaults_to_first_sighting() {
+        let mut debouncer = Debouncer::default();
+        let config = config(2, 1);
+
+        assert!(debouncer.update(vec![detection("a")], 10, config).is_empty());
+        let running = debouncer.update(vec![detection("a")], 20, config);
+        assert_eq!(running[0].started_at, Some(10));
+
+        let mut known = detection("b");
+        known.started_at = Some(5);
+        debouncer.update(vec![known.clone()], 30, config);
+        let running = debouncer.update(vec![known], 40, config);
+        assert_eq!(running.iter().map(|d| d.started_at).collect::<Vec<_>>(), vec![Some(5)]);
+    }
+}
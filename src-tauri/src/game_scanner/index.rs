@@ -0,0 +1,259 @@
+//! Lookup index from executable names to watch list entries.
+//!
+//! The detectable games list has tens of thousands of executables, so the scan
+//! loop resolves each process with a hash lookup instead of walking the list.
+//! Compare against the naive nested loop with:
+//!
+//! ```text
+//! cargo test --release -- --ignored --nocapture bench_index
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::emulator::{Emulator, Emulators};
+use super::matcher::{self, MatchOptions};
+use super::process::ProcessInfo;
+use super::{DetectableGame, GameExecutable};
+
+/// The scanner watch list together with its lookup index. The two are built
+/// together and swapped as a unit so a scan never sees them out of sync.
+#[derive(Default)]
+pub struct WatchList {
+    pub games: Vec<DetectableGame>,
+    /// Normalized executable file name -> (game index, executable index) of
+    /// every entry with that file name, in watch list order.
+    index: HashMap<String, Vec<(usize, usize)>>,
+    /// Steam app id -> index of the first game listing it.
+    steam_apps: HashMap<u32, usize>,
+    /// Emulators whose content is reported in place of the emulator itself.
+    emulators: Arc<Emulators>,
+}
+
+impl WatchList {
+    pub fn new(games: Vec<DetectableGame>) -> WatchList {
+        let mut index: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        let mut steam_apps = HashMap::new();
+        for (game_index, game) in games.iter().enumerate() {
+            let steam_ids = game
+                .third_party_skus
+                .iter()
+                .filter(|sku| sku.distributor == "steam")
+                .filter_map(|sku| sku.id.as_deref()?.parse().ok());
+            for app_id in steam_ids {
+                steam_apps.entry(app_id).or_insert(game_index);
+            }
+            for (exe_index, exe) in game.executables.iter().flatten().enumerate() {
+                index
+                    .entry(matcher::entry_key(&exe.name))
+                    .or_default()
+                    .push((game_index, exe_index));
+            }
+        }
+        WatchList {
+            games,
+            index,
+            steam_apps,
+            emulators: Arc::default(),
+        }
+    }
+
+    pub fn with_emulators(mut self, emulators: Arc<Emulators>) -> WatchList {
+        self.emulators = emulators;
+        self
+    }
+
+    /// The game `process` is emulating, along with the emulator. `keys` are
+    /// the process's [`ProcessInfo::lookup_keys`].
+    pub fn emulated_game(&self, process: &ProcessInfo, keys: &[String]) -> Option<(DetectableGame, &Emulator)> {
+        self.emulators.game(process, keys)
+    }
+
+    /// The game listed with Steam app id `app_id`.
+    pub fn steam_game(&self, app_id: u32) -> Option<&DetectableGame> {
+        self.steam_apps.get(&app_id).map(|&game_index| &self.games[game_index])
+    }
+
+    /// Finds the game and executable entry matching `process`, whose
+    /// [`ProcessInfo::lookup_keys`] are `keys`. When several entries match, the
+    /// earliest in the watch list wins.
+    pub fn lookup(
+        &self,
+        process: &ProcessInfo,
+        keys: &[String],
+        options: MatchOptions,
+    ) -> Option<(&DetectableGame, &GameExecutable)> {
+        keys.iter()
+            .filter_map(|key| self.index.get(key))
+            .flatten()
+            .copied()
+            .filter_map(|(game_index, exe_index)| {
+                let game = &self.games[game_index];
+                let exe = &game.executables.as_ref()?[exe_index];
+                if matcher::os_matches(&exe.os, process, options)
+                    && matcher::executable_matches(&exe.name, process)
+                    && matcher::arguments_match(exe.arguments.as_deref(), process)
+                {
+                    Some((game_index, game, exe))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|(game_index, _, _)| *game_index)
+            .map(|(_, game, exe)| (game, exe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn named(name: &str) -> ProcessInfo {
+        ProcessInfo {
+            name: name.to_string(),
+            ..ProcessInfo::default()
+        }
+    }
+
+    fn lookup<'a>(list: &'a WatchList, process: &ProcessInfo, options: MatchOptions) -> Option<(&'a DetectableGame, &'a GameExecutable)> {
+        list.lookup(process, &process.lookup_keys(), options)
+    }
+
+    fn game(id: &str, executables: &[&str]) -> DetectableGame {
+        DetectableGame {
+            id: id.to_string(),
+            name: format!("Game {}", id),
+            executables: Some(
+                executables
+                    .iter()
+                    .map(|name| GameExecutable {
+                        os: matcher::CURRENT_OS.to_string(),
+                        name: name.to_string(),
+                        ..GameExecutable::default()
+                    })
+                    .collect(),
+            ),
+            ..DetectableGame::default()
+        }
+    }
+
+    #[test]
+    fn lookup_ignores_case_and_exe_suffix() {
+        let list = WatchList::new(vec![game("1", &["Hades.exe"]), game("2", &["minecraft"])]);
+
+        assert_eq!(lookup(&list, &named("hades"), MatchOptions::default()).unwrap().0.id, "1");
+        assert_eq!(lookup(&list, &named("HADES.EXE"), MatchOptions::default()).unwrap().1.name, "Hades.exe");
+        assert_eq!(lookup(&list, &named("Minecraft.exe"), MatchOptions::default()).unwrap().0.id, "2");
+        assert!(lookup(&list, &named("hades2"), MatchOptions::default()).is_none());
+    }
+
+    #[test]
+    fn earlier_entries_take_precedence() {
+        let list = WatchList::new(vec![game("custom", &["launcher"]), game("fetched", &["launcher.exe"])]);
+
+        assert_eq!(lookup(&list, &named("launcher"), MatchOptions::default()).unwrap().0.id, "custom");
+    }
+
+    #[test]
+    fn path_entries_are_verified_against_the_process_path() {
+        let list = WatchList::new(vec![
+            game("1", &["steamapps/common/first/game.exe"]),
+            game("2", &["steamapps/common/second/game.exe"]),
+        ]);
+        let second = ProcessInfo {
+            name: "game.exe".to_string(),
+            exe: Some(r"C:\Steam\steamapps\common\Second\game.exe".to_string()),
+            ..ProcessInfo::default()
+        };
+
+        assert_eq!(lookup(&list, &second, MatchOptions::default()).unwrap().0.id, "2");
+        assert!(lookup(&list, &named("game.exe"), MatchOptions::default()).is_none());
+    }
+
+    #[test]
+    fn entries_for_other_systems_need_the_compatibility_layer() {
+        let mut windows_only = game("1", &["foo.exe"]);
+        for exe in windows_only.executables.iter_mut().flatten() {
+            exe.os = "win32".to_string();
+        }
+        let list = WatchList::new(vec![windows_only]);
+        let wine = named("Foo.exe");
+        let compat = MatchOptions {
+            compatibility_layer: true,
+        };
+
+        if matcher::CURRENT_OS == "win32" {
+            assert!(lookup(&list, &wine, MatchOptions::default()).is_some());
+        } else {
+            assert!(lookup(&list, &wine, MatchOptions::default()).is_none());
+            assert!(lookup(&list, &wine, compat).is_some());
+            assert!(lookup(&list, &named("foo"), compat).is_none());
+        }
+    }
+
+    #[test]
+    fn games_without_executables_are_skipped() {
+        let mut no_exes = game("1", &[]);
+        no_exes.executables = None;
+        let list = WatchList::new(vec![no_exes, game("2", &["a.exe"])]);
+
+        assert_eq!(list.games.len(), 2);
+        assert_eq!(lookup(&list, &named("a"), MatchOptions::default()).unwrap().0.id, "2");
+    }
+
+    /// The lookup the scan loop performed before the index existed.
+    fn nested_lookup<'a>(games: &'a [DetectableGame], process_name: &str) -> Option<&'a DetectableGame> {
+        for game in games {
+            for exe in game.executables.iter().flatten() {
+                let clean_exe = exe.name.trim_end_matches(".exe");
+                let clean_proc = process_name.trim_end_matches(".exe");
+                if clean_exe.eq_ignore_ascii_case(clean_proc) {
+                    return Some(game);
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_index() {
+        // ~20k executables, roughly the size of Discord's list.
+        let games: Vec<DetectableGame> = (0..10_000)
+            .map(|i| {
+                let first = format!("game{}.exe", i);
+                let second = format!("bin/launcher{}.exe", i);
+                game(&i.to_string(), &[&first, &second])
+            })
+            .collect();
+        // A typical desktop: a few hundred processes, one of them a game.
+        let mut processes: Vec<ProcessInfo> = (0..400).map(|i| named(&format!("process{}", i))).collect();
+        processes.push(named("game9999.exe"));
+
+        let start = Instant::now();
+        let list = WatchList::new(games.clone());
+        let build_time = start.elapsed();
+
+        let start = Instant::now();
+        let nested: Vec<_> = processes.iter().filter_map(|p| nested_lookup(&games, &p.name)).collect();
+        let nested_time = start.elapsed();
+
+        let start = Instant::now();
+        let indexed: Vec<_> = processes.iter().filter_map(|p| lookup(&list, p, MatchOptions::default())).collect();
+        let indexed_time = start.elapsed();
+
+        println!(
+            "{} processes x {} executables: nested {:?}, indexed {:?} (index built in {:?})",
+            processes.len(),
+            list.index.len(),
+            nested_time,
+            indexed_time,
+            build_time
+        );
+        assert_eq!(nested.len(), 1);
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(nested[0].id, indexed[0].0.id);
+        assert!(indexed_time < nested_time);
+    }
+}
@@ -0,0 +1,545 @@
+//! Games installed through Lutris, Heroic and Bottles on Linux.
+//!
+//! These launchers mostly run games that aren't on the detectable list, but
+//! they know each game's name and executable. [`LauncherLibraries`] reads
+//! their local configs into extra watch list entries and reloads them when the
+//! files change:
+//!
+//! - Lutris: one `<slug>-<timestamp>.yml` per game, naming its `exe`, with
+//!   the game names in the `pga.db` database.
+//! - Heroic: Legendary's `installed.json` for Epic games, and the GOG
+//!   `installed.json` plus each game's `goggame-<id>.info`.
+//! - Bottles: the `External_Programs` of each bottle's `bottle.yml`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use super::matcher::ANY_OS;
+use super::{DetectableGame, GameExecutable};
+
+/// Prefixes of the ids of games from each launcher.
+pub const LUTRIS_ID_PREFIX: &str = "lutris:";
+pub const HEROIC_ID_PREFIX: &str = "heroic:";
+pub const BOTTLES_ID_PREFIX: &str = "bottles:";
+
+/// How often the library files are checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where the launchers keep their libraries.
+#[derive(Clone, Debug, Default)]
+pub struct LibraryPaths {
+    /// Directories of Lutris game configs.
+    pub lutris: Vec<PathBuf>,
+    /// Lutris game databases.
+    pub lutris_db: Vec<PathBuf>,
+    /// Heroic config directories.
+    pub heroic: Vec<PathBuf>,
+    /// Directories holding one directory per bottle.
+    pub bottles: Vec<PathBuf>,
+}
+
+impl LibraryPaths {
+    /// The native and Flatpak install locations in the user's home directory.
+    #[cfg(target_os = "linux")]
+    pub fn discover() -> LibraryPaths {
+        let home = match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home),
+            None => return LibraryPaths::default(),
+        };
+        let existing = |paths: &[&str]| -> Vec<PathBuf> {
+            paths.iter().map(|path| home.join(path)).filter(|path| path.exists()).collect()
+        };
+        LibraryPaths {
+            lutris: existing(&[
+                ".config/lutris/games",
+                ".local/share/lutris/games",
+                ".var/app/net.lutris.Lutris/config/lutris/games",
+                ".var/app/net.lutris.Lutris/data/lutris/games",
+            ]),
+            lutris_db: existing(&[".local/share/lutris/pga.db", ".var/app/net.lutris.Lutris/data/lutris/pga.db"]),
+            heroic: existing(&[".config/heroic", ".var/app/com.heroicgameslauncher.hgl/config/heroic"]),
+            bottles: existing(&[
+                ".local/share/bottles/bottles",
+                ".var/app/com.usebottles.bottles/data/bottles/bottles",
+            ]),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn discover() -> LibraryPaths {
+        LibraryPaths::default()
+    }
+
+    /// The files the games are read from, and the directories listing them.
+    fn watched_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for dir in &self.lutris {
+            files.push(dir.clone());
+            files.extend(lutris_configs(dir));
+        }
+        files.extend(self.lutris_db.iter().cloned());
+        for dir in &self.heroic {
+            files.push(legendary_installed(dir));
+            files.push(gog_installed(dir));
+        }
+        for dir in &self.bottles {
+            files.push(dir.clone());
+            files.extend(subdirectories(dir).into_iter().map(|bottle| bottle.join("bottle.yml")));
+        }
+        files
+    }
+}
+
+/// The launcher libraries merged into the watch list.
+pub struct LauncherLibraries {
+    paths: LibraryPaths,
+    /// Modification times of the watched files as of the last load.
+    stamps: Vec<Option<SystemTime>>,
+    checked_at: Option<Instant>,
+    games: Vec<DetectableGame>,
+}
+
+impl LauncherLibraries {
+    pub fn new(paths: LibraryPaths) -> LauncherLibraries {
+        LauncherLibraries {
+            paths,
+            stamps: Vec::new(),
+            checked_at: None,
+            games: Vec::new(),
+        }
+    }
+
+    pub fn games(&self) -> &[DetectableGame] {
+        &self.games
+    }
+
+    /// Reloads the games if a library file changed since the last load,
+    /// checking at most every [`CHECK_INTERVAL`]. Returns whether the games changed.
+    pub fn refresh(&mut self) -> bool {
+        if self.checked_at.map_or(false, |checked| checked.elapsed() < CHECK_INTERVAL) {
+            return false;
+        }
+        self.checked_at = Some(Instant::now());
+
+        let stamps: Vec<Option<SystemTime>> = self
+            .paths
+            .watched_files()
+            .iter()
+            .map(|file| fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+            .collect();
+        if stamps == self.stamps {
+            return false;
+        }
+        self.stamps = stamps;
+        let games = load_games(&self.paths);
+        if games == self.games {
+            return false;
+        }
+        self.games = games;
+        log::info!("Loaded {} games from launcher libraries", self.games.len());
+        true
+    }
+}
+
+/// Reads the games of every library in `paths`.
+pub fn load_games(paths: &LibraryPaths) -> Vec<DetectableGame> {
+    let names = lutris_names(&paths.lutris_db);
+    let lutris = paths.lutris.iter().flat_map(|dir| lutris_games(dir, &names));
+    let heroic = paths.heroic.iter().flat_map(|dir| heroic_games(dir));
+    let bottles = paths.bottles.iter().flat_map(|dir| bottles_games(dir));
+    lutris.chain(heroic).chain(bottles).collect()
+}
+
+/// A watch list entry for a game run from `executable`. The entry is the file
+/// name and its directory, which also lines up with the Windows path Wine
+/// reports for programs inside a prefix.
+fn library_game(id: String, name: &str, executable: &Path) -> Option<DetectableGame> {
+    let file_name = executable.file_name()?.to_str()?;
+    let entry = match executable.parent().and_then(Path::file_name).and_then(|dir| dir.to_str()) {
+        Some(dir) => format!("{}/{}", dir, file_name),
+        None => file_name.to_string(),
+    };
+    Some(DetectableGame {
+        id,
+        name: name.trim().to_string(),
+        executables: Some(vec![GameExecutable {
+            os: ANY_OS.to_string(),
+            name: entry,
+            ..GameExecutable::default()
+        }]),
+        ..DetectableGame::default()
+    })
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()).collect())
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+fn lutris_configs(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map_or(false, |ext| ext == "yml"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Names of the games in the Lutris `databases`, keyed by config file stem and
+/// by slug.
+pub fn lutris_names(databases: &[PathBuf]) -> BTreeMap<String, String> {
+    let mut names = BTreeMap::new();
+    for path in databases {
+        let rows = match read_lutris_games(path) {
+            Ok(rows) => rows,
+            Err(e) => {
+                log::warn!("Failed to read Lutris database {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for row in rows {
+            let name = match row.name {
+                Some(name) if !name.trim().is_empty() => name,
+                _ => continue,
+            };
+            if let Some(config) = row.configpath {
+                names.insert(config, name.clone());
+            }
+            if let Some(slug) = row.slug {
+                names.entry(slug).or_insert(name);
+            }
+        }
+    }
+    names
+}
+
+/// A game in the Lutris database.
+struct LutrisRow {
+    name: Option<String>,
+    slug: Option<String>,
+    /// The stem of the game's config file.
+    configpath: Option<String>,
+}
+
+fn read_lutris_games(path: &Path) -> rusqlite::Result<Vec<LutrisRow>> {
+    // Lutris may be running, so the database is only ever read.
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    let mut statement = connection.prepare("SELECT name, slug, configpath FROM games")?;
+    let rows = statement.query_map([], |row| {
+        Ok(LutrisRow {
+            name: row.get(0)?,
+            slug: row.get(1)?,
+            configpath: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Reads the YAML config at `path`, or `None` if it is missing or malformed.
+fn read_yaml(path: &Path) -> Option<Value> {
+    let text = fs::read_to_string(path).ok()?;
+    serde_yaml::from_str(&text)
+        .map_err(|e| log::warn!("Ignoring invalid config {}: {}", path.display(), e))
+        .ok()
+}
+
+/// The Lutris games configured in `dir`. Each is named by the `name` key of
+/// its config, or else by `names` from the Lutris database, falling back to
+/// its slug when neither knows it.
+pub fn lutris_games(dir: &Path, names: &BTreeMap<String, String>) -> Vec<DetectableGame> {
+    lutris_configs(dir)
+        .iter()
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?;
+            let slug = lutris_slug(stem);
+            let config = read_yaml(path)?;
+            let exe = config.get("game")?.get("exe")?.as_str()?;
+            let name = config
+                .get("name")
+                .and_then(Value::as_str)
+                .filter(|name| !name.trim().is_empty())
+                .map(str::to_string)
+                .or_else(|| names.get(stem).or_else(|| names.get(slug)).cloned())
+                .unwrap_or_else(|| title_case(slug));
+            library_game(format!("{}{}", LUTRIS_ID_PREFIX, slug), &name, Path::new(exe))
+        })
+        .collect()
+}
+
+/// The game slug of a Lutris config file name, `<slug>-<timestamp>`.
+fn lutris_slug(stem: &str) -> &str {
+    match stem.rsplit_once('-') {
+        Some((slug, timestamp)) if !slug.is_empty() && timestamp.bytes().all(|b| b.is_ascii_digit()) => slug,
+        _ => stem,
+    }
+}
+
+fn title_case(slug: &str) -> String {
+    slug.split('-')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn legendary_installed(heroic: &Path) -> PathBuf {
+    heroic.join("legendaryConfig/legendary/installed.json")
+}
+
+fn gog_installed(heroic: &Path) -> PathBuf {
+    heroic.join("gog_store/installed.json")
+}
+
+#[derive(Deserialize)]
+struct LegendaryGame {
+    app_name: String,
+    title: String,
+    install_path: PathBuf,
+    executable: String,
+}
+
+#[derive(Deserialize)]
+struct GogInstalled {
+    installed: Vec<GogGame>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogGame {
+    app_name: String,
+    #[serde(rename = "install_path")]
+    install_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogInfo {
+    name: String,
+    #[serde(default)]
+    play_tasks: Vec<GogPlayTask>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GogPlayTask {
+    #[serde(default)]
+    is_primary: bool,
+    #[serde(default)]
+    path: Option<String>,
+}
+
+/// The Epic and GOG games installed through the Heroic config in `heroic`.
+pub fn heroic_games(heroic: &Path) -> Vec<DetectableGame> {
+    let mut games = Vec::new();
+
+    let legendary: Option<std::collections::BTreeMap<String, LegendaryGame>> = fs::read(legendary_installed(heroic))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    for game in legendary.into_iter().flat_map(|installed| installed.into_values()) {
+        let executable = game.install_path.join(&game.executable);
+        games.extend(library_game(format!("{}{}", HEROIC_ID_PREFIX, game.app_name), &game.title, &executable));
+    }
+
+    let gog: Option<GogInstalled> = fs::read(gog_installed(heroic))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    for game in gog.into_iter().flat_map(|gog| gog.installed) {
+        // The name and executable are only in the game's own info file.
+        let info: Option<GogInfo> = fs::read(game.install_path.join(format!("goggame-{}.info", game.app_name)))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok());
+        let info = match info {
+            Some(info) => info,
+            None => continue,
+        };
+        let task = info.play_tasks.iter().find(|task| task.is_primary && task.path.is_some());
+        if let Some(path) = task.and_then(|task| task.path.as_deref()) {
+            let executable = game.install_path.join(path.replace('\\', "/"));
+            games.extend(library_game(format!("{}{}", HEROIC_ID_PREFIX, game.app_name), &info.name, &executable));
+        }
+    }
+    games
+}
+
+/// The programs added to the bottles in `dir`.
+pub fn bottles_games(dir: &Path) -> Vec<DetectableGame> {
+    let mut games = Vec::new();
+    for bottle in subdirectories(dir) {
+        let config = match read_yaml(&bottle.join("bottle.yml")) {
+            Some(config) => config,
+            None => continue,
+        };
+        let bottle_name = config.get("Name").and_then(Value::as_str).unwrap_or_default();
+        let programs = config.get("External_Programs").and_then(Value::as_mapping);
+        for (id, program) in programs.into_iter().flatten() {
+            let id = id.as_str();
+            let name = program.get("name").and_then(Value::as_str);
+            let path = program.get("path").and_then(Value::as_str);
+            if let (Some(id), Some(name), Some(path)) = (id, name, path) {
+                let id = format!("{}{}:{}", BOTTLES_ID_PREFIX, bottle_name, id);
+                games.extend(library_game(id, name, Path::new(path)));
+            }
+        }
+    }
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::test_support::temp_dir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// (id, name, executable entry) of each game.
+    fn summary(games: &[DetectableGame]) -> Vec<(&str, &str, &str)> {
+        games
+            .iter()
+            .map(|game| (game.id.as_str(), game.name.as_str(), game.executables.as_ref().unwrap()[0].name.as_str()))
+            .collect()
+    }
+
+    /// A Lutris games directory with the fixture configs, and its database.
+    fn lutris_library(dir: &Path) -> (PathBuf, PathBuf) {
+        let games = dir.join("games");
+        write(&games.join("hollow-knight-1612345678.yml"), include_str!("fixtures/lutris/hollow-knight-1612345678.yml"));
+        write(&games.join("celeste-1612345679.yml"), include_str!("fixtures/lutris/celeste-1612345679.yml"));
+        write(&games.join("retroarch-snes-1612345680.yml"), include_str!("fixtures/lutris/retroarch-snes-1612345680.yml"));
+        write(
+            &games.join("the-witcher-3-wild-hunt-1612345681.yml"),
+            include_str!("fixtures/lutris/the-witcher-3-wild-hunt-1612345681.yml"),
+        );
+        let db = dir.join("pga.db");
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(include_str!("fixtures/lutris/pga.sql"))
+            .unwrap();
+        (games, db)
+    }
+
+    #[test]
+    fn reads_lutris_game_configs() {
+        let dir = temp_dir("lutris");
+        let (games, db) = lutris_library(&dir);
+        write(
+            &games.join("ori-1612345682.yml"),
+            "name: \"Ori and the Will of the Wisps\\u2122\"\ngame:\n  exe: /home/user/Games/ori/drive_c/Ori/oriwotw.exe\n",
+        );
+
+        assert_eq!(
+            summary(&lutris_games(&games, &lutris_names(&[db]))),
+            vec![
+                // Celeste isn't in the database, so it is named from its slug.
+                ("lutris:celeste", "Celeste", "Celeste/Celeste.bin.x86_64"),
+                ("lutris:hollow-knight", "Hollow Knight", "Hollow Knight/hollow_knight.exe"),
+                ("lutris:ori", "Ori and the Will of the Wisps\u{2122}", "Ori/oriwotw.exe"),
+                ("lutris:the-witcher-3-wild-hunt", "The Witcher 3: Wild Hunt", "x64/witcher3.exe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn lutris_names_fall_back_to_the_slug() {
+        let dir = temp_dir("lutris-no-db");
+        let (games, _) = lutris_library(&dir);
+
+        let games = lutris_games(&games, &lutris_names(&[PathBuf::from("/nonexistent/pga.db")]));
+        let names: Vec<&str> = games.iter().map(|game| game.name.as_str()).collect();
+        assert_eq!(names, vec!["Celeste", "Hollow Knight", "The Witcher 3 Wild Hunt"]);
+    }
+
+    #[test]
+    fn reads_heroic_epic_and_gog_installs() {
+        let heroic = temp_dir("heroic");
+        let disco = heroic.join("Disco Elysium");
+        write(&legendary_installed(&heroic), include_str!("fixtures/heroic/legendary_installed.json"));
+        write(
+            &gog_installed(&heroic),
+            &include_str!("fixtures/heroic/gog_installed.json").replace("/mnt/games/Heroic/Disco Elysium", disco.to_str().unwrap()),
+        );
+        write(&disco.join("goggame-1771589310.info"), include_str!("fixtures/heroic/goggame-1771589310.info"));
+
+        assert_eq!(
+            summary(&heroic_games(&heroic)),
+            vec![
+                ("heroic:Fortnite", "Fortnite", "Win64/FortniteLauncher.exe"),
+                ("heroic:Salt", "Hades", "x64/Hades.exe"),
+                ("heroic:1771589310", "Disco Elysium", "Disco Elysium/disco.exe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_bottles_programs() {
+        let dir = temp_dir("bottles");
+        write(&dir.join("Gaming/bottle.yml"), include_str!("fixtures/bottles/bottle.yml"));
+        fs::create_dir_all(dir.join("Empty")).unwrap();
+
+        assert_eq!(
+            summary(&bottles_games(&dir)),
+            vec![
+                (
+                    "bottles:Gaming:7a3c0d8e-2f6b-4f7e-9d0a-3c5e1b2a4f60",
+                    "Hollow Knight",
+                    "Hollow Knight/hollow_knight.exe"
+                ),
+                ("bottles:Gaming:b91e77d2-5d4a-4c3b-8e1f-0a2b3c4d5e6f", "Installer", "Downloads/setup.exe"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reloads_when_library_files_change() {
+        let dir = temp_dir("libraries");
+        let mut libraries = LauncherLibraries::new(LibraryPaths {
+            lutris: vec![dir.to_path_buf()],
+            ..LibraryPaths::default()
+        });
+        assert!(!libraries.refresh());
+        assert!(libraries.games().is_empty());
+
+        write(&dir.join("celeste-1612345679.yml"), include_str!("fixtures/lutris/celeste-1612345679.yml"));
+        // Checks are rate limited.
+        assert!(!libraries.refresh());
+        libraries.checked_at = None;
+        assert!(libraries.refresh());
+        assert_eq!(libraries.games().len(), 1);
+        libraries.checked_at = None;
+        assert!(!libraries.refresh());
+
+        // Touching a config without changing its game isn't a change either.
+        write(&dir.join("celeste-1612345679.yml"), include_str!("fixtures/lutris/celeste-1612345679.yml"));
+        libraries.stamps.clear();
+        libraries.checked_at = None;
+        assert!(!libraries.refresh());
+        assert_eq!(libraries.games().len(), 1);
+    }
+
+    #[test]
+    fn no_launchers_installed_is_not_a_change() {
+        let mut libraries = LauncherLibraries::new(LibraryPaths::default());
+        assert!(!libraries.refresh());
+        libraries.checked_at = None;
+        assert!(!libraries.refresh());
+    }
+}
@@ -0,0 +1,377 @@
+//! Matching of running processes against detectable executable entries.
+//!
+//! Entries in the detectable list are either bare executable names
+//! (`hades.exe`) or trailing path fragments (`steamapps/common/foo/foo.exe`).
+//! A fragment matches when it lines up with the last components of the
+//! process's executable path or `argv[0]`, so `common/foo/foo.exe` matches
+//! `C:\Steam\steamapps\common\Foo\foo.exe` but not `D:\Other\foo.exe`.
+//!
+//! Entries prefixed with `>` must match the process's file name exactly:
+//! no parent directories and no `.exe` folding between Windows and Unix names.
+//!
+//! Entries with `arguments` additionally require the process's command line
+//! to contain those arguments, in order, as when several games share one
+//! engine executable.
+//!
+//! On macOS, an entry naming an application bundle (`foo.app`) matches any
+//! process running from inside that bundle.
+//!
+//! Each entry also names the OS it applies to. Only entries for the current OS
+//! are considered, unless [`MatchOptions::compatibility_layer`] is set, in
+//! which case Windows entries also match processes running under Wine or Proton.
+
+use super::process::ProcessInfo;
+
+/// The current OS as named in the detectable games list.
+#[cfg(target_os = "windows")]
+pub const CURRENT_OS: &str = "win32";
+#[cfg(target_os = "macos")]
+pub const CURRENT_OS: &str = "darwin";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub const CURRENT_OS: &str = "linux";
+
+/// Entries that apply to every OS, such as user-defined games.
+pub const ANY_OS: &str = "all";
+
+/// Settings that change how entries are matched.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MatchOptions {
+    /// Also match Windows entries against processes running under Wine or Proton.
+    pub compatibility_layer: bool,
+}
+
+impl ProcessInfo {
+    /// Whether the process looks like a Windows program running under Wine or Proton.
+    pub fn runs_under_wine(&self) -> bool {
+        let is_wine_tool = |path: &str| {
+            let path = normalize_path(path);
+            let name = components(&path).last().copied().unwrap_or("");
+            name.starts_with("wine") || name == "proton"
+        };
+        let is_windows_path = |path: &str| {
+            let bytes = path.as_bytes();
+            bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\'
+        };
+
+        // Wine names processes after the Windows executable.
+        self.name.to_lowercase().ends_with(".exe")
+            || self.exe.as_deref().map_or(false, is_wine_tool)
+            || self.cmd.first().map_or(false, |argv0| is_windows_path(argv0))
+            || self.cmd.iter().any(|arg| is_wine_tool(arg))
+            || self.parent_name.as_deref().map_or(false, is_wine_tool)
+    }
+
+    /// Normalized paths identifying the process: its executable path,
+    /// `argv[0]` (which is the Windows path for processes under Wine) and its name.
+    fn paths(&self) -> Vec<String> {
+        self.exe
+            .iter()
+            .chain(self.cmd.first())
+            .chain(std::iter::once(&self.name))
+            .filter(|path| !path.is_empty())
+            .map(|path| normalize_path(path))
+            .collect()
+    }
+
+    /// Keys under which entries that could match this process are indexed:
+    /// the file names of its paths and the names of any enclosing `.app` bundles.
+    pub fn lookup_keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        for path in self.paths() {
+            let components = components(&path);
+            let bundles = components.iter().filter(|c| c.ends_with(".app"));
+            for key in components.last().into_iter().chain(bundles) {
+                let key = normalize_file_name(key);
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+}
+
+/// Lowercases `path` and converts Windows separators so paths from every OS compare alike.
+pub fn normalize_path(path: &str) -> String {
+    path.to_lowercase().replace('\\', "/")
+}
+
+/// Normalizes a single file name: case-insensitive, with `.exe` stripped so
+/// Windows entries match Wine process names.
+pub fn normalize_file_name(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+/// The key an executable entry is indexed under: its normalized file name.
+pub fn entry_key(entry: &str) -> String {
+    let entry = normalize_path(entry);
+    let entry = entry.trim_start_matches('>');
+    normalize_file_name(components(entry).last().copied().unwrap_or(""))
+}
+
+fn components(path: &str) -> Vec<&str> {
+    path.split('/').filter(|c| !c.is_empty()).collect()
+}
+
+/// Whether an entry for `entry_os` may match `process` on this system.
+pub fn os_matches(entry_os: &str, process: &ProcessInfo, options: MatchOptions) -> bool {
+    os_matches_on(CURRENT_OS, entry_os, process, options)
+}
+
+fn os_matches_on(current_os: &str, entry_os: &str, process: &ProcessInfo, options: MatchOptions) -> bool {
+    entry_os == ANY_OS
+        || entry_os == current_os
+        || (options.compatibility_layer
+            && entry_os == "win32"
+            && current_os != "win32"
+            && process.runs_under_wine())
+}
+
+/// Whether the executable entry `entry` matches `process`.
+pub fn executable_matches(entry: &str, process: &ProcessInfo) -> bool {
+    let entry = normalize_path(entry);
+    let paths = process.paths();
+
+    if let Some(exact) = entry.strip_prefix('>') {
+        return paths
+            .iter()
+            .any(|path| components(path).last() == Some(&exact));
+    }
+
+    let wanted = components(&entry);
+    if wanted.is_empty() {
+        return false;
+    }
+
+    paths.iter().any(|path| {
+        let path = components(path);
+        // The full path, and the path of each enclosing `.app` bundle.
+        let bundles = path
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.ends_with(".app"))
+            .map(|(i, _)| &path[..=i]);
+        std::iter::once(&path[..])
+            .chain(bundles)
+            .any(|candidate| ends_with_components(candidate, &wanted))
+    })
+}
+
+/// Whether the command line of `process` contains `arguments`, compared
+/// case-insensitively word by word. Entries without arguments always match;
+/// entries with arguments never match processes whose command line is unknown.
+pub fn arguments_match(arguments: Option<&str>, process: &ProcessInfo) -> bool {
+    let wanted: Vec<String> = arguments
+        .unwrap_or("")
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect();
+    if wanted.is_empty() {
+        return true;
+    }
+    let args: Vec<String> = process
+        .cmd
+        .iter()
+        .skip(1)
+        .flat_map(|arg| arg.split_whitespace())
+        .map(str::to_lowercase)
+        .collect();
+    args.windows(wanted.len()).any(|window| window == &wanted[..])
+}
+
+/// Whether `path` ends with the components of `wanted`, folding `.exe` on the file name.
+fn ends_with_components(path: &[&str], wanted: &[&str]) -> bool {
+    if wanted.len() > path.len() {
+        return false;
+    }
+    let tail = &path[path.len() - wanted.len()..];
+    let (wanted_name, wanted_dirs) = wanted.split_last().unwrap();
+    let (tail_name, tail_dirs) = tail.split_last().unwrap();
+    wanted_dirs == tail_dirs && normalize_file_name(wanted_name) == normalize_file_name(tail_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(name: &str, exe: Option<&str>, cmd: &[&str]) -> ProcessInfo {
+        ProcessInfo {
+            name: name.to_string(),
+            exe: exe.map(str::to_string),
+            cmd: cmd.iter().map(|arg| arg.to_string()).collect(),
+            ..ProcessInfo::default()
+        }
+    }
+
+    #[test]
+    fn executable_matching() {
+        let windows_steam = process(
+            "Foo.exe",
+            Some(r"C:\Program Files (x86)\Steam\steamapps\common\Foo\Foo.exe"),
+            &[r"C:\Program Files (x86)\Steam\steamapps\common\Foo\Foo.exe", "-windowed"],
+        );
+        let windows_other = process("foo.exe", Some(r"D:\Games\Other\foo.exe"), &[]);
+        let macos_bundle = process(
+            "Foo",
+            Some("/Applications/Foo Game.app/Contents/MacOS/Foo"),
+            &["/Applications/Foo Game.app/Contents/MacOS/Foo"],
+        );
+        let linux_native = process(
+            "foo.x86_64",
+            Some("/home/user/.local/share/Steam/steamapps/common/Foo/foo.x86_64"),
+            &["/home/user/.local/share/Steam/steamapps/common/Foo/foo.x86_64"],
+        );
+        let linux_wine = process(
+            "wine64-preloader",
+            Some("/usr/bin/wine64-preloader"),
+            &[r"Z:\home\user\Games\Foo\Foo.exe"],
+        );
+        let name_only = process("launcher", None, &[]);
+
+        let cases: &[(&str, &ProcessInfo, bool)] = &[
+            // Bare names match the file name, folding case and `.exe`.
+            ("foo.exe", &windows_steam, true),
+            ("FOO", &windows_steam, true),
+            ("foo.x86_64", &linux_native, true),
+            ("foo.exe", &linux_wine, true),
+            ("launcher", &name_only, true),
+            ("bar.exe", &windows_steam, false),
+            // Path fragments must line up with trailing directories.
+            ("steamapps/common/foo/foo.exe", &windows_steam, true),
+            ("common/foo/foo.exe", &windows_steam, true),
+            ("common/foo/foo.exe", &windows_other, false),
+            ("steamapps/common/foo/foo.x86_64", &linux_native, true),
+            ("games/foo/foo.exe", &linux_wine, true),
+            ("teamapps/common/foo/foo.exe", &windows_steam, false),
+            ("foo/foo.exe", &name_only, false),
+            // `>` requires the exact file name.
+            (">foo.exe", &windows_steam, true),
+            (">foo", &windows_steam, false),
+            (">launcher", &name_only, true),
+            (">foo.exe", &linux_native, false),
+            // Application bundles match processes inside them.
+            ("foo game.app", &macos_bundle, true),
+            ("foo game.app/contents/macos/foo", &macos_bundle, true),
+            ("foo", &macos_bundle, true),
+            ("bar.app", &macos_bundle, false),
+        ];
+
+        for (entry, process, expected) in cases {
+            assert_eq!(
+                executable_matches(entry, process),
+                *expected,
+                "entry {:?} against {:?}",
+                entry,
+                process
+            );
+        }
+    }
+
+    #[test]
+    fn wine_detection() {
+        let mut proton_child = process("GameThread", Some("/home/user/Games/Foo/foo"), &[]);
+        proton_child.parent_name = Some("wineserver".to_string());
+
+        let cases: &[(ProcessInfo, bool)] = &[
+            (process("Foo.exe", None, &[]), true),
+            (process("wine64-preloader", Some("/usr/bin/wine64-preloader"), &[]), true),
+            (process("foo", None, &[r"C:\Games\Foo\foo.exe"]), true),
+            (
+                process(
+                    "python3",
+                    None,
+                    &["python3", "/home/user/.steam/steamapps/common/Proton 8.0/proton", "waitforexitandrun"],
+                ),
+                true,
+            ),
+            (proton_child, true),
+            (process("launcher", Some("/usr/bin/launcher"), &["/usr/bin/launcher"]), false),
+            (
+                ProcessInfo {
+                    parent_name: Some("bash".to_string()),
+                    ..process("foo.x86_64", Some("/home/user/Games/Foo/foo.x86_64"), &[])
+                },
+                false,
+            ),
+        ];
+
+        for (process, expected) in cases {
+            assert_eq!(process.runs_under_wine(), *expected, "{:?}", process);
+        }
+    }
+
+    #[test]
+    fn os_filtering() {
+        let native = process("launcher", Some("/usr/bin/launcher"), &[]);
+        let wine = process("Launcher.exe", None, &[r"C:\Games\launcher.exe"]);
+        let strict = MatchOptions::default();
+        let compat = MatchOptions {
+            compatibility_layer: true,
+        };
+
+        let cases: &[(&str, &str, &ProcessInfo, MatchOptions, bool)] = &[
+            ("linux", "linux", &native, strict, true),
+            ("linux", "all", &native, strict, true),
+            ("linux", "win32", &native, strict, false),
+            ("linux", "darwin", &native, strict, false),
+            ("linux", "win32", &wine, strict, false),
+            ("linux", "win32", &wine, compat, true),
+            ("linux", "win32", &native, compat, false),
+            ("darwin", "win32", &wine, compat, true),
+            ("darwin", "linux", &wine, compat, false),
+            ("win32", "win32", &native, strict, true),
+            ("win32", "linux", &wine, compat, false),
+        ];
+
+        for (current_os, entry_os, process, options, expected) in cases {
+            assert_eq!(
+                os_matches_on(current_os, entry_os, process, *options),
+                *expected,
+                "{} entry on {} for {:?} ({:?})",
+                entry_os,
+                current_os,
+                process,
+                options
+            );
+        }
+    }
+
+    #[test]
+    fn entry_keys_and_lookup_keys_line_up() {
+        let cases: &[(&str, &str)] = &[
+            ("Foo.exe", "foo"),
+            (">Foo.exe", "foo"),
+            (r"steamapps\common\Foo\Foo.exe", "foo"),
+            ("Foo Game.app", "foo game.app"),
+            ("", ""),
+        ];
+        for (entry, key) in cases {
+            assert_eq!(entry_key(entry), *key, "entry {:?}", entry);
+        }
+
+        let bundle = process("Foo", Some("/Applications/Foo Game.app/Contents/MacOS/Foo"), &[]);
+        assert_eq!(bundle.lookup_keys(), vec!["foo", "foo game.app"]);
+    }
+
+    #[test]
+    fn arguments_matching() {
+        let source_game = process("hl2_linux", None, &["./hl2_linux", "-game", "CSTRIKE", "-steam"]);
+        let bare = process("hl2_linux", None, &["./hl2_linux"]);
+        let unknown = process("hl2_linux", None, &[]);
+
+        assert!(arguments_match(None, &bare));
+        assert!(arguments_match(Some(" "), &unknown));
+        assert!(arguments_match(Some("-game cstrike"), &source_game));
+        assert!(arguments_match(Some("-steam"), &source_game));
+        assert!(!arguments_match(Some("-game tf"), &source_game));
+        assert!(!arguments_match(Some("cstrike -game"), &source_game));
+        assert!(!arguments_match(Some("-game cstrike"), &bare));
+        assert!(!arguments_match(Some("-game cstrike"), &unknown));
+        // argv[0] is the program, not an argument.
+        assert!(!arguments_match(Some("./hl2_linux"), &bare));
+    }
+}
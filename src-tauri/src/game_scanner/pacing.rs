@@ -0,0 +1,214 @@
+//! How long the scan loop waits between scans.
+//!
+//! With a fixed interval the loop simply sleeps for [`ScanInterval::seconds`].
+//! In adaptive mode the [`Pacer`] shortens the wait for a few scans after a
+//! game starts or stops and when the app gains focus, so transitions are
+//! confirmed quickly, and stretches it while the system runs on battery or the
+//! user has been idle.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Scans run at the fast interval after a change or focus.
+pub const FAST_SCANS: u32 = 4;
+/// Shortest wait between scans in adaptive mode.
+const MIN_FAST_SECONDS: u64 = 2;
+/// Longest wait when backing off.
+const MAX_BACKOFF_SECONDS: u64 = 120;
+/// User input idle time after which the scanner backs off.
+pub const IDLE_AFTER_SECONDS: u64 = 5 * 60;
+
+/// The configured scan interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScanInterval {
+    pub seconds: u64,
+    /// Scan faster after changes and slower on battery or when idle.
+    pub adaptive: bool,
+}
+
+impl Default for ScanInterval {
+    fn default() -> Self {
+        ScanInterval {
+            seconds: 15,
+            adaptive: false,
+        }
+    }
+}
+
+/// What the system was doing when a scan finished.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Conditions {
+    pub focused: bool,
+    pub on_battery: bool,
+    /// Seconds since the last user input, if the platform reports it.
+    pub idle_seconds: Option<u64>,
+}
+
+/// Picks the wait before the next scan.
+#[derive(Debug, Default)]
+pub struct Pacer {
+    fast_scans_left: u32,
+    was_focused: bool,
+}
+
+impl Pacer {
+    /// The wait after a scan that found changes when `changed` is set.
+    pub fn next_delay(&mut self, interval: ScanInterval, changed: bool, conditions: Conditions) -> Duration {
+        let base = interval.seconds.max(1);
+        let gained_focus = conditions.focused && !self.was_focused;
+        self.was_focused = conditions.focused;
+        if !interval.adaptive {
+            return Duration::from_secs(base);
+        }
+
+        if changed || gained_focus {
+            self.fast_scans_left = FAST_SCANS;
+        }
+        let idle = conditions.idle_seconds.map_or(false, |idle| idle >= IDLE_AFTER_SECONDS);
+        let seconds = if self.fast_scans_left > 0 {
+            self.fast_scans_left -= 1;
+            (base / 3).max(MIN_FAST_SECONDS).min(base)
+        } else if conditions.on_battery || idle {
+            (base * 4).min(MAX_BACKOFF_SECONDS).max(base)
+        } else {
+            base
+        };
+        Duration::from_secs(seconds)
+    }
+}
+
+/// Timings of the process listing, for tuning the interval.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ScanMetrics {
+    pub scans: u64,
+    /// Duration of the last process refresh in milliseconds.
+    pub last_ms: f64,
+    /// Moving average of the refresh duration in milliseconds.
+    pub average_ms: f64,
+    pub max_ms: f64,
+    /// The wait chosen after the last scan, in seconds.
+    pub next_scan_in: u64,
+}
+
+impl ScanMetrics {
+    pub fn record(&mut self, refresh: Duration, next_scan_in: Duration) {
+        let ms = refresh.as_secs_f64() * 1000.0;
+        self.average_ms = if self.scans == 0 {
+            ms
+        } else {
+            // Weight recent scans so the average follows changes in load.
+            self.average_ms * 0.9 + ms * 0.1
+        };
+        self.scans += 1;
+        self.last_ms = ms;
+        self.max_ms = self.max_ms.max(ms);
+        self.next_scan_in = next_scan_in.as_secs();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delays(interval: ScanInterval, scans: &[(bool, Conditions)]) -> Vec<u64> {
+        let mut pacer = Pacer::default();
+        scans
+            .iter()
+            .map(|&(changed, conditions)| pacer.next_delay(interval, changed, conditions).as_secs())
+            .collect()
+    }
+
+    fn adaptive(seconds: u64) -> ScanInterval {
+        ScanInterval {
+            seconds,
+            adaptive: true,
+        }
+    }
+
+    const PLUGGED_IN: Conditions = Conditions {
+        focused: false,
+        on_battery: false,
+        idle_seconds: None,
+    };
+
+    #[test]
+    fn fixed_interval_ignores_conditions() {
+        let on_battery = Conditions {
+            on_battery: true,
+            ..PLUGGED_IN
+        };
+        assert_eq!(
+            delays(ScanInterval::default(), &[(true, PLUGGED_IN), (false, on_battery)]),
+            vec![15, 15]
+        );
+    }
+
+    #[test]
+    fn changes_and_focus_speed_up_a_few_scans() {
+        let focused = Conditions {
+            focused: true,
+            ..PLUGGED_IN
+        };
+        let scans = [
+            (false, PLUGGED_IN),
+            (true, PLUGGED_IN),
+            (false, PLUGGED_IN),
+            (false, PLUGGED_IN),
+            (false, PLUGGED_IN),
+            (false, PLUGGED_IN),
+            (false, focused),
+            (false, focused),
+        ];
+        assert_eq!(delays(adaptive(15), &scans), vec![15, 5, 5, 5, 5, 15, 5, 5]);
+    }
+
+    #[test]
+    fn backs_off_on_battery_or_when_idle() {
+        let on_battery = Conditions {
+            on_battery: true,
+            ..PLUGGED_IN
+        };
+        let idle = Conditions {
+            idle_seconds: Some(IDLE_AFTER_SECONDS),
+            ..PLUGGED_IN
+        };
+        assert_eq!(delays(adaptive(15), &[(false, on_battery), (false, idle)]), vec![60, 60]);
+        assert_eq!(delays(adaptive(60), &[(false, on_battery)]), vec![120]);
+        assert_eq!(delays(adaptive(300), &[(false, on_battery)]), vec![300]);
+        assert_eq!(delays(adaptive(1), &[(true, on_battery)]), vec![1]);
+    }
+
+    #[test]
+    fn idle_time_backs_off_only_past_the_threshold() {
+        let idle = |seconds| Conditions {
+            idle_seconds: seconds,
+            ..PLUGGED_IN
+        };
+        let idle_on_battery = Conditions {
+            on_battery: true,
+            ..idle(Some(IDLE_AFTER_SECONDS))
+        };
+        let scans = [
+            (false, idle(None)),
+            (false, idle(Some(IDLE_AFTER_SECONDS - 1))),
+            (false, idle(Some(IDLE_AFTER_SECONDS))),
+            (false, idle_on_battery),
+            (false, idle(Some(0))),
+        ];
+        assert_eq!(delays(adaptive(15), &scans), vec![15, 15, 60, 60, 15]);
+    }
+
+    #[test]
+    fn metrics_track_refresh_durations() {
+        let mut metrics = ScanMetrics::default();
+        metrics.record(Duration::from_millis(20), Duration::from_secs(15));
+        metrics.record(Duration::from_millis(10), Duration::from_secs(5));
+
+        assert_eq!(metrics.scans, 2);
+        assert_eq!(metrics.last_ms, 10.0);
+        assert_eq!(metrics.max_ms, 20.0);
+        assert!((metrics.average_ms - 19.0).abs() < 1e-9);
+        assert_eq!(metrics.next_scan_in, 5);
+    }
+}
@@ -0,0 +1,245 @@
+//! Best-effort probes of the power source and user idle time, used by the
+//! adaptive scan interval. Platforms without a cheap probe report "plugged
+//! in" and unknown idle time, which keeps the configured interval:
+//!
+//! - Linux reads the power source from `/sys/class/power_supply`. Idle time
+//!   isn't available without a display server specific API, so it is unknown.
+//! - macOS asks `pmset` and `ioreg`.
+//! - Windows calls `GetSystemPowerStatus` and `GetLastInputInfo`.
+//!
+//! Spawning `pmset` and `ioreg` costs more than a scan, so [`PowerProbe`]
+//! keeps the results for [`PROBE_INTERVAL`] rather than asking on every scan,
+//! and probes on a blocking thread rather than on the scanner task.
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+/// How long probe results are reused.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The power source and idle time as of the last probe.
+pub struct PowerProbe {
+    probe: fn() -> (bool, Option<u64>),
+    last: Option<(Instant, bool, Option<u64>)>,
+    /// The results of a probe still running.
+    pending: Option<Receiver<(bool, Option<u64>)>>,
+}
+
+impl Default for PowerProbe {
+    fn default() -> Self {
+        PowerProbe {
+            probe: || (on_battery(), idle_seconds()),
+            last: None,
+            pending: None,
+        }
+    }
+}
+
+impl PowerProbe {
+    /// Whether the system runs on battery, and the seconds since the last user
+    /// input if known, as of the last probe. Once those results are
+    /// [`PROBE_INTERVAL`] old a new probe starts, and its results are returned
+    /// from the first read after it finishes. Until the first probe finishes,
+    /// the system counts as plugged in with unknown idle time.
+    pub fn read(&mut self) -> (bool, Option<u64>) {
+        if let Some(pending) = &self.pending {
+            match pending.try_recv() {
+                Ok((on_battery, idle)) => {
+                    self.last = Some((Instant::now(), on_battery, idle));
+                    self.pending = None;
+                }
+                Err(TryRecvError::Disconnected) => self.pending = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        let stale = self.last.map_or(true, |(probed_at, ..)| probed_at.elapsed() >= PROBE_INTERVAL);
+        if stale && self.pending.is_none() {
+            let (tx, rx) = mpsc::channel();
+            let probe = self.probe;
+            tauri::async_runtime::spawn_blocking(move || {
+                let _ = tx.send(probe());
+            });
+            self.pending = Some(rx);
+        }
+        // The user may have come back since, so the idle time isn't extrapolated.
+        self.last.map_or((false, None), |(_, on_battery, idle)| (on_battery, idle))
+    }
+}
+
+/// Whether the system is running on battery power.
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    on_battery_in(std::path::Path::new("/sys/class/power_supply"))
+}
+
+/// Whether the power supplies listed in `dir` include a battery and no
+/// connected external supply.
+#[cfg(target_os = "linux")]
+fn on_battery_in(dir: &std::path::Path) -> bool {
+    let supplies = match std::fs::read_dir(dir) {
+        Ok(supplies) => supplies,
+        Err(_) => return false,
+    };
+    let mut has_battery = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap_or_default();
+        match read("type").trim() {
+            "Mains" | "USB" if read("online").trim() == "1" => return false,
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    has_battery
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map_or(false, |output| {
+            String::from_utf8_lossy(&output.stdout).contains("'Battery Power'")
+        })
+}
+
+#[cfg(windows)]
+pub fn on_battery() -> bool {
+    let mut status = win32::SystemPowerStatus::default();
+    // SAFETY: `status` is a valid SYSTEM_POWER_STATUS for the call to fill in.
+    let ok = unsafe { win32::GetSystemPowerStatus(&mut status) } != 0;
+    // ACLineStatus: 0 offline, 1 online, 255 unknown.
+    ok && status.ac_line_status == 0
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn on_battery() -> bool {
+    false
+}
+
+/// Seconds since the last keyboard or mouse input.
+#[cfg(target_os = "macos")]
+pub fn idle_seconds() -> Option<u64> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let line = output.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+    let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+    Some(nanos / 1_000_000_000)
+}
+
+#[cfg(windows)]
+pub fn idle_seconds() -> Option<u64> {
+    let mut info = win32::LastInputInfo {
+        cb_size: std::mem::size_of::<win32::LastInputInfo>() as u32,
+        dw_time: 0,
+    };
+    // SAFETY: `info` is a valid LASTINPUTINFO with its size set, as the call requires.
+    if unsafe { win32::GetLastInputInfo(&mut info) } == 0 {
+        return None;
+    }
+    // Both are milliseconds since boot in 32 bits, which wrap after 49.7 days.
+    // SAFETY: GetTickCount takes no arguments and can't fail.
+    let now = unsafe { win32::GetTickCount() };
+    Some(u64::from(now.wrapping_sub(info.dw_time)) / 1000)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn idle_seconds() -> Option<u64> {
+    None
+}
+
+#[cfg(windows)]
+mod win32 {
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct SystemPowerStatus {
+        pub ac_line_status: u8,
+        pub battery_flag: u8,
+        pub battery_life_percent: u8,
+        pub system_status_flag: u8,
+        pub battery_life_time: u32,
+        pub battery_full_life_time: u32,
+    }
+
+    #[repr(C)]
+    pub struct LastInputInfo {
+        pub cb_size: u32,
+        pub dw_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+        pub fn GetTickCount() -> u32;
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        pub fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Reads until the running probe has finished.
+    fn finish_probe(power: &mut PowerProbe) {
+        while power.pending.is_some() {
+            std::thread::sleep(Duration::from_millis(1));
+            power.read();
+        }
+    }
+
+    #[test]
+    fn probes_are_reused_until_stale() {
+        static PROBES: AtomicUsize = AtomicUsize::new(0);
+        let mut power = PowerProbe {
+            probe: || {
+                PROBES.fetch_add(1, Ordering::SeqCst);
+                (true, Some(600))
+            },
+            last: None,
+            pending: None,
+        };
+
+        assert_eq!(power.read(), (false, None));
+        finish_probe(&mut power);
+        assert_eq!(power.read(), (true, Some(600)));
+        assert_eq!(power.read(), (true, Some(600)));
+        assert_eq!(PROBES.load(Ordering::SeqCst), 1);
+
+        // Stale results are returned while the next probe runs.
+        let stale = Instant::now().checked_sub(PROBE_INTERVAL).unwrap();
+        power.last = Some((stale, false, None));
+        assert_eq!(power.read(), (false, None));
+        finish_probe(&mut power);
+        assert_eq!(power.read(), (true, Some(600)));
+        assert_eq!(PROBES.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_linux_power_supplies() {
+        use crate::game_scanner::test_support::temp_dir;
+        use std::fs;
+
+        let dir = temp_dir("power");
+        let supply = |name: &str, kind: &str, online: &str| {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join("type"), format!("{}\n", kind)).unwrap();
+            fs::write(dir.join(name).join("online"), format!("{}\n", online)).unwrap();
+        };
+        // A desktop without a battery.
+        assert!(!on_battery_in(&dir));
+        supply("BAT0", "Battery", "");
+        supply("AC", "Mains", "0");
+        assert!(on_battery_in(&dir));
+        supply("AC", "Mains", "1");
+        assert!(!on_battery_in(&dir));
+    }
+}
@@ -0,0 +1,71 @@
+//! Games the user doesn't want to share.
+//!
+//! Ignored games are still detected, so their sessions reach the local
+//! history, but they are never reported to the frontend as activity and are
+//! passed over when choosing the primary activity.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::DetectableGame;
+
+/// Category of the games the user added themselves.
+pub const CUSTOM_CATEGORY: &str = "custom";
+
+/// Game ids and categories hidden from shared activity.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IgnoreList {
+    pub games: BTreeSet<String>,
+    /// Lowercase category names, see [`categories`].
+    pub categories: BTreeSet<String>,
+}
+
+impl IgnoreList {
+    /// Whether activity for `game` must not be shared.
+    pub fn hides(&self, game: &DetectableGame) -> bool {
+        self.games.contains(&game.id)
+            || (!self.categories.is_empty()
+                && categories(game).iter().any(|category| self.categories.contains(category)))
+    }
+}
+
+/// The lowercase categories `game` belongs to: its themes from the detectable
+/// list, plus [`CUSTOM_CATEGORY`] for user-defined games.
+pub fn categories(game: &DetectableGame) -> Vec<String> {
+    let mut categories: Vec<String> = game.themes.iter().map(|theme| theme.trim().to_lowercase()).collect();
+    if game.id.starts_with(super::custom::CUSTOM_ID_PREFIX) {
+        categories.push(CUSTOM_CATEGORY.to_string());
+    }
+    categories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(id: &str, themes: &[&str]) -> DetectableGame {
+        DetectableGame {
+            id: id.to_string(),
+            name: id.to_string(),
+            executables: None,
+            themes: themes.iter().map(|theme| theme.to_string()).collect(),
+            ..DetectableGame::default()
+        }
+    }
+
+    #[test]
+    fn hides_games_by_id_or_category() {
+        let ignored = IgnoreList {
+            games: ["123".to_string()].into_iter().collect(),
+            categories: ["horror".to_string(), CUSTOM_CATEGORY.to_string()].into_iter().collect(),
+        };
+
+        assert!(ignored.hides(&game("123", &[])));
+        assert!(ignored.hides(&game("456", &["Action", "Horror"])));
+        assert!(ignored.hides(&game("custom:notes", &[])));
+        assert!(!ignored.hides(&game("789", &["Action"])));
+        assert!(!IgnoreList::default().hides(&game("123", &["Horror"])));
+    }
+}
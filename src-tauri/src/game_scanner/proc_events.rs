@@ -0,0 +1,353 @@
+//! Event-driven wake-ups of the scan loop on Linux.
+//!
+//! The kernel's process connector reports every `exec` and exit over a netlink
+//! socket. The [`Listener`] checks each exec against the watch list and wakes
+//! the scanner through [`ScannerState::wake`] when a watched process starts
+//! or one it saw start exits, so detection doesn't wait for the next poll.
+//!
+//! Subscribing needs `CAP_NET_ADMIN`, so [`Listener::start`] fails for most
+//! desktop sessions; the scanner then keeps polling on its interval alone.
+//! Polling also stays on as a safety net while the listener runs, catching
+//! processes that rename themselves after `exec`, as Wine does.
+
+#![cfg_attr(not(target_os = "linux"), allow(dead_code))]
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use super::index::WatchList;
+use super::matcher::MatchOptions;
+use super::ScannerState;
+
+/// A process lifecycle event from the connector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcEvent {
+    Exec(u32),
+    Exit(u32),
+}
+
+// Message layout from linux/netlink.h, linux/connector.h and linux/cn_proc.h.
+const NLMSG_HDRLEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+const NLMSG_DONE: u16 = 3;
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+/// Offset of the event payload inside `struct proc_event`.
+const EVENT_DATA_OFFSET: usize = 16;
+
+/// Reads the `u32` at `offset`, copying it out of `buf`. Headers are parsed
+/// this way rather than by casting the buffer to `nlmsghdr` or `cn_msg`, so
+/// received bytes need no particular alignment, and a short read yields `None`.
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Parses the events in one datagram from the connector. Thread events are
+/// dropped: only execs and exits of whole processes are reported.
+pub fn parse_events(buf: &[u8]) -> Vec<ProcEvent> {
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while let Some(len) = read_u32(buf, offset).map(|len| len as usize) {
+        if len < NLMSG_HDRLEN || offset + len > buf.len() {
+            break;
+        }
+        let event = offset + NLMSG_HDRLEN + CN_MSG_LEN;
+        let data = event + EVENT_DATA_OFFSET;
+        if let (Some(what), Some(pid), Some(tgid)) = (read_u32(buf, event), read_u32(buf, data), read_u32(buf, data + 4)) {
+            match what {
+                PROC_EVENT_EXEC => events.push(ProcEvent::Exec(tgid)),
+                PROC_EVENT_EXIT if pid == tgid => events.push(ProcEvent::Exit(tgid)),
+                _ => {}
+            }
+        }
+        // Messages are padded to 4 bytes.
+        offset += (len + 3) & !3;
+    }
+    events
+}
+
+/// A running connector subscription. Dropping it tells the listener thread to
+/// stop without waiting for it: the thread may be blocked in `recv` for up to
+/// its receive timeout, and the scanner task that drops it mustn't stall.
+pub struct Listener {
+    stop: Arc<AtomicBool>,
+}
+
+impl Listener {
+    /// Subscribes to process events and starts waking `scanner` when a process
+    /// from the latest watch list published on `matching` starts or exits.
+    pub fn start(
+        matching: watch::Receiver<(Arc<WatchList>, MatchOptions)>,
+        scanner: ScannerState,
+    ) -> io::Result<Listener> {
+        let socket = imp::Socket::subscribe()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        std::thread::Builder::new()
+            .name("game-scanner-proc-events".to_string())
+            .spawn(move || listen(socket, matching, scanner, thread_stop))?;
+        Ok(Listener { stop })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn listen(
+    socket: imp::Socket,
+    matching: watch::Receiver<(Arc<WatchList>, MatchOptions)>,
+    scanner: ScannerState,
+    stop: Arc<AtomicBool>,
+) {
+    use std::collections::HashSet;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    use super::{process, steam};
+
+    /// Shortest time between two wake-ups, so bursts of events cost one scan.
+    const MIN_WAKE_INTERVAL: Duration = Duration::from_millis(500);
+
+    let proc_root = Path::new("/proc");
+    let is_watched = |watch_list: &WatchList, options: MatchOptions, pid: u32| {
+        process::read_proc(proc_root, pid).map_or(false, |process| {
+            let keys = process.lookup_keys();
+            watch_list.lookup(&process, &keys, options).is_some()
+                || steam::app_id(&process).is_some()
+                || watch_list.emulated_game(&process, &keys).is_some()
+        })
+    };
+
+    let mut buf = vec![0u8; 4096];
+    let mut watched: HashSet<u32> = HashSet::new();
+    let mut current: Option<(Arc<WatchList>, bool)> = None;
+    let mut pending = false;
+    let mut last_wake: Option<Instant> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        let (watch_list, options) = matching.borrow().clone();
+
+        // Re-seed the watched pids whenever what counts as watched changes.
+        let unchanged = current.as_ref().map_or(false, |(list, compat)| {
+            Arc::ptr_eq(list, &watch_list) && *compat == options.compatibility_layer
+        });
+        if !unchanged {
+            watched = process::list_pids(proc_root)
+                .into_iter()
+                .filter(|&pid| is_watched(&watch_list, options, pid))
+                .collect();
+            current = Some((watch_list.clone(), options.compatibility_layer));
+        }
+
+        match socket.recv(&mut buf) {
+            Ok(len) => {
+                for event in parse_events(&buf[..len]) {
+                    match event {
+                        ProcEvent::Exec(pid) => {
+                            if is_watched(&watch_list, options, pid) {
+                                watched.insert(pid);
+                                pending = true;
+                            }
+                        }
+                        ProcEvent::Exit(pid) => pending |= watched.remove(&pid),
+                    }
+                }
+            }
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {}
+            Err(e) => {
+                log::warn!("Process event listener stopped: {}", e);
+                break;
+            }
+        }
+
+        if pending && last_wake.map_or(true, |last| last.elapsed() >= MIN_WAKE_INTERVAL) {
+            pending = false;
+            last_wake = Some(Instant::now());
+            if scanner.wake().is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listen(
+    socket: imp::Socket,
+    _matching: watch::Receiver<(Arc<WatchList>, MatchOptions)>,
+    _scanner: ScannerState,
+    _stop: Arc<AtomicBool>,
+) {
+    match socket {}
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::io;
+    use std::mem;
+
+    use super::*;
+
+    /// A netlink connector socket subscribed to process events.
+    pub struct Socket(libc::c_int);
+
+    impl Socket {
+        pub fn subscribe() -> io::Result<Socket> {
+            // SAFETY: socket takes no pointers; failure is reported as -1.
+            let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, libc::NETLINK_CONNECTOR) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let socket = Socket(fd);
+
+            // SAFETY: sockaddr_nl is plain integers, for which all zeroes is valid.
+            let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+            addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            addr.nl_groups = CN_IDX_PROC;
+            // SAFETY: `addr` is a live sockaddr_nl and the length passed is its size.
+            let bound = unsafe {
+                libc::bind(
+                    fd,
+                    &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                )
+            };
+            if bound < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Wake up every second so the listener notices when it's stopped.
+            let timeout = libc::timeval {
+                tv_sec: 1,
+                tv_usec: 0,
+            };
+            // SAFETY: `timeout` is a live timeval and the length passed is its size.
+            let set = unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_RCVTIMEO,
+                    &timeout as *const libc::timeval as *const libc::c_void,
+                    mem::size_of::<libc::timeval>() as libc::socklen_t,
+                )
+            };
+            if set < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let message = listen_message();
+            // SAFETY: the pointer and length describe `message`, which outlives the call.
+            let sent = unsafe { libc::send(fd, message.as_ptr() as *const libc::c_void, message.len(), 0) };
+            if sent < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(socket)
+        }
+
+        pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+            // SAFETY: the kernel writes at most `buf.len()` bytes into `buf`, which is
+            // borrowed mutably for the call. The result is never more than that
+            // length, so `buf[..len]` stays in bounds.
+            let len = unsafe { libc::recv(self.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if len < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(len as usize)
+            }
+        }
+    }
+
+    impl Drop for Socket {
+        fn drop(&mut self) {
+            // SAFETY: `self.0` is a socket this value owns and nothing else closes.
+            unsafe {
+                libc::close(self.0);
+            }
+        }
+    }
+
+    /// The `PROC_CN_MCAST_LISTEN` request: a netlink header, a connector
+    /// header and the operation.
+    fn listen_message() -> Vec<u8> {
+        let len = NLMSG_HDRLEN + CN_MSG_LEN + 4;
+        let mut message = Vec::with_capacity(len);
+        message.extend_from_slice(&(len as u32).to_ne_bytes());
+        message.extend_from_slice(&NLMSG_DONE.to_ne_bytes());
+        message.extend_from_slice(&0u16.to_ne_bytes()); // flags
+        message.extend_from_slice(&0u32.to_ne_bytes()); // seq
+        message.extend_from_slice(&std::process::id().to_ne_bytes());
+        message.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        message.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes()); // seq
+        message.extend_from_slice(&0u32.to_ne_bytes()); // ack
+        message.extend_from_slice(&4u16.to_ne_bytes()); // payload length
+        message.extend_from_slice(&0u16.to_ne_bytes()); // flags
+        message.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+        message
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io;
+
+    pub enum Socket {}
+
+    impl Socket {
+        pub fn subscribe() -> io::Result<Socket> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "process events are only available on Linux",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a connector message carrying a `proc_event`.
+    fn message(what: u32, pid: u32, tgid: u32) -> Vec<u8> {
+        let len = NLMSG_HDRLEN + CN_MSG_LEN + EVENT_DATA_OFFSET + 24;
+        let mut message = vec![0u8; len];
+        message[..4].copy_from_slice(&(len as u32).to_ne_bytes());
+        let event = NLMSG_HDRLEN + CN_MSG_LEN;
+        message[event..event + 4].copy_from_slice(&what.to_ne_bytes());
+        let data = event + EVENT_DATA_OFFSET;
+        message[data..data + 4].copy_from_slice(&pid.to_ne_bytes());
+        message[data + 4..data + 8].copy_from_slice(&tgid.to_ne_bytes());
+        message
+    }
+
+    #[test]
+    fn parses_process_execs_and_exits() {
+        const PROC_EVENT_FORK: u32 = 1;
+        let datagram = [
+            message(PROC_EVENT_EXEC, 42, 42),
+            message(PROC_EVENT_FORK, 43, 43),
+            message(PROC_EVENT_EXIT, 51, 50),
+            message(PROC_EVENT_EXIT, 50, 50),
+        ]
+        .concat();
+
+        assert_eq!(parse_events(&datagram), vec![ProcEvent::Exec(42), ProcEvent::Exit(50)]);
+    }
+
+    #[test]
+    fn truncated_messages_are_ignored() {
+        let message = message(PROC_EVENT_EXEC, 42, 42);
+        assert!(parse_events(&message[..message.len() - 1]).is_empty());
+        assert!(parse_events(&[1, 2]).is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
+use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+
+/// A running process as seen by the scanner.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// Path of the process executable, if the OS exposes it.
+    pub exe: Option<String>,
+    pub cmd: Vec<String>,
+    /// Name of the parent process, if it is still running.
+    pub parent_name: Option<String>,
+    /// Unix timestamp (seconds) the process started at, if known.
+    pub start_time: Option<u64>,
+}
+
+impl ProcessInfo {
+    pub fn from_sysinfo(process: &sysinfo::Process, sys: &System) -> ProcessInfo {
+        let exe = process.exe();
+        let exe = if exe.as_os_str().is_empty() {
+            None
+        } else {
+            Some(exe.to_string_lossy().into_owned())
+        };
+        let name = process.name().to_string();
+        // sysinfo reads the name from `/proc/<pid>/comm`.
+        #[cfg(target_os = "linux")]
+        let name = untruncated_name(&name, exe.as_deref(), process.cmd());
+        ProcessInfo {
+            pid: process.pid().as_u32(),
+            name,
+            exe,
+            cmd: process.cmd().to_vec(),
+            parent_name: process
+                .parent()
+                .and_then(|pid| sys.process(pid))
+                .map(|parent| parent.name().to_string()),
+            start_time: Some(process.start_time()).filter(|&time| time > 0),
+        }
+    }
+}
+
+/// Reads process `pid` from the procfs mounted at `root`, without its parent
+/// or start time. Returns `None` if the process is gone.
+#[cfg(target_os = "linux")]
+pub fn read_proc(root: &Path, pid: u32) -> Option<ProcessInfo> {
+    let dir = root.join(pid.to_string());
+    let name = fs::read_to_string(dir.join("comm")).ok()?;
+    let cmd: Vec<String> = fs::read(dir.join("cmdline"))
+        .map(|data| {
+            data.split(|&byte| byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let exe = fs::read_link(dir.join("exe"))
+        .ok()
+        .map(|exe| exe.to_string_lossy().into_owned());
+    Some(ProcessInfo {
+        pid,
+        name: untruncated_name(name.trim_end_matches('\n'), exe.as_deref(), &cmd),
+        exe,
+        cmd,
+        ..ProcessInfo::default()
+    })
+}
+
+/// Length Linux truncates process names in `/proc/<pid>/comm` to, in bytes.
+#[cfg(target_os = "linux")]
+pub const COMM_MAX_LEN: usize = 15;
+
+/// The full name of a process whose `comm` name may have been truncated: a
+/// name of exactly [`COMM_MAX_LEN`] bytes is replaced by the file name of the
+/// executable path or `argv[0]` that it is the start of. Under Wine the
+/// executable is the loader, so `argv[0]`, the Windows path, is what matches.
+#[cfg(target_os = "linux")]
+pub fn untruncated_name(name: &str, exe: Option<&str>, cmd: &[String]) -> String {
+    if name.len() != COMM_MAX_LEN {
+        return name.to_string();
+    }
+    exe.into_iter()
+        .chain(cmd.first().map(String::as_str))
+        .filter_map(|path| path.rsplit(['/', '\\']).find(|c| !c.is_empty()))
+        .find(|file_name| file_name.len() > name.len() && file_name.starts_with(name))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// Pids of the processes in the procfs mounted at `root`.
+#[cfg(target_os = "linux")]
+pub fn list_pids(root: &Path) -> Vec<u32> {
+    fs::read_dir(root)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Something that can list the running processes.
+pub trait ProcessSource: Send {
+    /// Takes a fresh snapshot of the running processes.
+    fn snapshot(&mut self) -> Vec<ProcessInfo>;
+}
+
+/// Lists processes through `sysinfo`.
+pub struct SysinfoSource {
+    sys: System,
+}
+
+impl SysinfoSource {
+    /// Creates the source without reading anything; processes are listed on
+    /// the first snapshot, and nothing else `sysinfo` can report is needed.
+    pub fn new() -> SysinfoSource {
+        SysinfoSource { sys: System::new() }
+    }
+}
+
+impl ProcessSource for SysinfoSource {
+    fn snapshot(&mut self) -> Vec<ProcessInfo> {
+        // Use specific refresh kind to prevent MacOS Objective-C null pointer panic
+        // from trying to fetch restricted process environments.
+        self.sys
+            .refresh_processes_specifics(sysinfo::ProcessRefreshKind::new());
+
+        self.sys
+            .processes()
+            .values()
+            .map(|process| ProcessInfo::from_sysinfo(process, &self.sys))
+            .collect()
+    }
+}
+
+/// Replays a fixed sequence of snapshots, then reports no processes.
+#[cfg(test)]
+pub struct ScriptedSource {
+    snapshots: std::collections::VecDeque<Vec<ProcessInfo>>,
+}
+
+#[cfg(test)]
+impl ScriptedSource {
+    pub fn new(snapshots: Vec<Vec<ProcessInfo>>) -> ScriptedSource {
+        ScriptedSource {
+            snapshots: snapshots.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ProcessSource for ScriptedSource {
+    fn snapshot(&mut self) -> Vec<ProcessInfo> {
+        self.snapshots.pop_front().unwrap_or_default()
+    }
+}
+
+/// Reports the processes last set through any of its clones, however often
+/// it is asked. The scanner task also scans on its own schedule, so tests
+/// driving it set what is running rather than script each scan.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct SharedSource {
+    processes: std::sync::Arc<std::sync::Mutex<Vec<ProcessInfo>>>,
+}
+
+#[cfg(test)]
+impl SharedSource {
+    pub fn set(&self, processes: Vec<ProcessInfo>) {
+        *self.processes.lock().unwrap() = processes;
+    }
+}
+
+#[cfg(test)]
+impl ProcessSource for SharedSource {
+    fn snapshot(&mut self) -> Vec<ProcessInfo> {
+        self.processes.lock().unwrap().clone()
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::game_scanner::matcher::executable_matches;
+    use crate::game_scanner::test_support::temp_dir;
+
+    /// Writes the `/proc/<pid>` entries the scanner reads.
+    fn add_process(root: &Path, pid: u32, comm: &str, cmdline: &[&str], exe: Option<&str>) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        fs::write(dir.join("cmdline"), cmdline.join("\0") + "\0").unwrap();
+        if let Some(exe) = exe {
+            std::os::unix::fs::symlink(exe, dir.join("exe")).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_processes_from_a_proc_tree() {
+        let root = temp_dir("proc");
+        fs::create_dir_all(root.join("self")).unwrap();
+        add_process(&root, 42, "hades", &["/games/hades/hades", "--fullscreen"], Some("/games/hades/hades"));
+
+        assert_eq!(list_pids(&root), vec![42]);
+        let process = read_proc(&root, 42).unwrap();
+        assert_eq!(process.pid, 42);
+        assert_eq!(process.name, "hades");
+        assert_eq!(process.exe.as_deref(), Some("/games/hades/hades"));
+        assert_eq!(process.cmd, vec!["/games/hades/hades", "--fullscreen"]);
+        assert!(read_proc(&root, 7).is_none());
+    }
+
+    #[test]
+    fn truncated_names_are_completed_from_the_exe_or_argv0() {
+        let root = temp_dir("proc-comm");
+        let hollow_knight = "/games/Hollow Knight/HollowKnight.x86_64";
+        add_process(&root, 1, "HollowKnight.x8", &[hollow_knight], Some(hollow_knight));
+        // The exe can't be read for processes of other users.
+        add_process(&root, 2, "HollowKnight.x8", &[hollow_knight, "-force-vulkan"], None);
+        // Under Wine the exe is the loader and argv[0] the Windows path.
+        add_process(
+            &root,
+            3,
+            "Cyberpunk2077.e",
+            &["C:\\Games\\Cyberpunk 2077\\bin\\x64\\Cyberpunk2077.exe"],
+            Some("/usr/bin/wine64-preloader"),
+        );
+        // A name is kept when no exe or argv[0] file name extends it, as for a name
+        // of exactly fifteen bytes or a program that rewrites argv[0].
+        add_process(&root, 4, "gnome-calculato", &["/usr/bin/gnome-calculator"], Some("/usr/bin/gnome-calculator"));
+        add_process(&root, 5, "fifteen-letters", &["/opt/fifteen-letters"], Some("/opt/fifteen-letters"));
+        add_process(&root, 6, "postgres-worker", &["postgres: checkpointer"], None);
+
+        let name = |pid| read_proc(&root, pid).unwrap().name;
+        assert_eq!(name(1), "HollowKnight.x86_64");
+        assert_eq!(name(2), "HollowKnight.x86_64");
+        assert_eq!(name(3), "Cyberpunk2077.exe");
+        assert_eq!(name(4), "gnome-calculator");
+        assert_eq!(name(5), "fifteen-letters");
+        assert_eq!(name(6), "postgres-worker");
+
+        let process = read_proc(&root, 2).unwrap();
+        assert!(executable_matches(">HollowKnight.x86_64", &process));
+        assert!(!executable_matches(">HollowKnight.x8", &process));
+    }
+}
@@ -0,0 +1,947 @@
+//! The scanner task.
+//!
+//! A single [`Scanner`] owns all scanner state and runs on its own task. Tauri
+//! commands, the games list refresh and the process event listener reach it
+//! only through [`Command`]s sent via a [`ScannerState`] handle, so every
+//! command sees a consistent snapshot and there are no locks to poison.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::Serialize;
+use tauri::async_runtime::JoinHandle;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+use super::activity::RunningGame;
+use super::cache::{self, GamesCache};
+use super::emulator::Emulators;
+use super::engine::{Detector, ScanEvent};
+use super::index::WatchList;
+use super::libraries::{LauncherLibraries, LibraryPaths};
+use super::matcher::MatchOptions;
+use super::pacing::{Conditions, Pacer, ScanMetrics};
+use super::power::PowerProbe;
+use super::process::ProcessSource;
+use super::settings::ScannerSettings;
+use super::status::{ErrorKind, ScannerError, ScannerStatus, StatusTracker};
+use super::steam::SteamLibrary;
+use super::{custom, history, proc_events, unix_now};
+use super::{DetectableGame, GameActivity, GameActivitySet, GamesListUpdate};
+
+const STOPPED: &str = "game scanner is not running";
+
+/// A request to the scanner task.
+pub enum Command {
+    SetEnabled(bool),
+    /// Scan now and reply with the result.
+    Rescan(oneshot::Sender<Result<GameActivitySet, String>>),
+    /// Change the configuration or state.
+    Configure(Box<dyn FnOnce(&mut Scanner) + Send>),
+    /// Read the state.
+    Query(Box<dyn FnOnce(&Scanner) + Send>),
+    /// Scan as soon as possible.
+    Wake,
+    /// End every activity and stop the task, replying once done.
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Handle to the scanner task, managed as Tauri state.
+#[derive(Clone)]
+pub struct ScannerState {
+    /// Replaced by [`ScannerState::reopen`] when a new task takes over.
+    commands: Arc<Mutex<mpsc::UnboundedSender<Command>>>,
+    /// The running scanner task, once started.
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Wakes the games list refresh task, e.g. when its interval changes.
+    pub list_refresh: Arc<tokio::sync::Notify>,
+    /// Shared by every games list request.
+    pub http: reqwest::Client,
+}
+
+/// The scanner's end of the command channel, until [`super::start`] spawns the task.
+pub struct ScannerInbox {
+    commands: mpsc::UnboundedReceiver<Command>,
+    settings: ScannerSettings,
+}
+
+impl ScannerState {
+    /// Creates the handle and the inbox the scanner task will read from.
+    pub fn new(settings: ScannerSettings) -> (ScannerState, ScannerInbox) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let state = ScannerState {
+            commands: Arc::new(Mutex::new(tx)),
+            task: Arc::default(),
+            list_refresh: Arc::new(tokio::sync::Notify::new()),
+            http: cache::http_client(),
+        };
+        (state, ScannerInbox { commands: rx, settings })
+    }
+
+    /// Opens a new command channel for a scanner task replacing one that has
+    /// exited. Every clone of this handle sends to the new task from now on.
+    pub fn reopen(&self, settings: ScannerSettings) -> ScannerInbox {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.commands.lock().unwrap_or_else(PoisonError::into_inner) = tx;
+        ScannerInbox { commands: rx, settings }
+    }
+
+    /// Keeps `task`, the spawned scanner task, to tell whether it is still running.
+    pub fn set_task(&self, task: JoinHandle<()>) {
+        *self.task.lock().unwrap_or_else(PoisonError::into_inner) = Some(task);
+    }
+
+    /// Whether the scanner task has exited, e.g. after a panic.
+    pub fn is_finished(&self) -> bool {
+        let task = self.task.lock().unwrap_or_else(PoisonError::into_inner);
+        task.as_ref().map_or(false, |task| task.inner().is_finished())
+    }
+
+    fn send(&self, command: Command) -> Result<(), String> {
+        self.commands
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(command)
+            .map_err(|_| STOPPED.to_string())
+    }
+
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), String> {
+        self.send(Command::SetEnabled(enabled))
+    }
+
+    pub fn wake(&self) -> Result<(), String> {
+        self.send(Command::Wake)
+    }
+
+    /// Stops the scanner task, reporting running games as stopped and
+    /// recording their sessions first. Returns once the task is done.
+    pub async fn shutdown(&self) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::Shutdown(tx))?;
+        rx.await.map_err(|_| STOPPED.to_string())
+    }
+
+    pub async fn rescan(&self) -> Result<GameActivitySet, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::Rescan(tx))?;
+        rx.await.map_err(|_| STOPPED.to_string())?
+    }
+
+    /// Applies `change` on the scanner task without waiting for it.
+    pub fn update(&self, change: impl FnOnce(&mut Scanner) + Send + 'static) -> Result<(), String> {
+        self.send(Command::Configure(Box::new(change)))
+    }
+
+    /// Applies `change` on the scanner task and returns its result.
+    pub async fn configure<T: Send + 'static>(
+        &self,
+        change: impl FnOnce(&mut Scanner) -> T + Send + 'static,
+    ) -> Result<T, String> {
+        let (tx, rx) = oneshot::channel();
+        self.update(move |scanner| {
+            let _ = tx.send(change(scanner));
+        })?;
+        rx.await.map_err(|_| STOPPED.to_string())
+    }
+
+    /// Reads from the scanner's state.
+    pub async fn query<T: Send + 'static>(&self, read: impl FnOnce(&Scanner) -> T + Send + 'static) -> Result<T, String> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::Query(Box::new(move |scanner| {
+            let _ = tx.send(read(scanner));
+        })))?;
+        rx.await.map_err(|_| STOPPED.to_string())
+    }
+}
+
+/// The app the scanner runs in: the frontend it reports to and the games list
+/// refresh it starts. Tests run the scanner against a host of their own.
+pub trait Host: Send {
+    /// Sends `payload` to the frontend as `event`.
+    fn emit(&self, event: &str, payload: serde_json::Value);
+
+    /// Starts refreshing the games list in the background, beginning from `cached`.
+    fn refresh_games_list(&self, scanner: ScannerState, cached: Option<GamesCache>) -> JoinHandle<Result<(), String>>;
+}
+
+/// Where the scanner keeps its files. Files without a known location are
+/// neither read nor written.
+#[derive(Clone, Debug, Default)]
+pub struct ScannerPaths {
+    pub settings: Option<PathBuf>,
+    pub custom_games: Option<PathBuf>,
+    /// The user's emulator definitions.
+    pub emulators: Option<PathBuf>,
+    /// The games list cache.
+    pub cache: Option<PathBuf>,
+    pub history: Option<PathBuf>,
+}
+
+/// The state owned by the scanner task.
+pub struct Scanner {
+    host: Box<dyn Host>,
+    pub paths: ScannerPaths,
+    pub handle: ScannerState,
+    pub settings: ScannerSettings,
+    pub fetched_games: Vec<DetectableGame>,
+    pub custom_games: Vec<DetectableGame>,
+    /// The fetched list merged with `custom_games`; this is what the scanner matches against.
+    pub watch_list: Arc<WatchList>,
+    /// Every game detected as running by the last scan, and the primary one among them.
+    pub detector: Detector,
+    /// Activity set by the user, reported instead of the detected primary until cleared.
+    pub manual_activity: Option<RunningGame>,
+    pub status: StatusTracker,
+    pub metrics: ScanMetrics,
+    /// Whether an app window has focus, for the adaptive scan interval.
+    pub app_focused: bool,
+    /// Subscription to process events, while one is active.
+    process_events: Option<proc_events::Listener>,
+    /// The watch list and match options, published for the process event listener.
+    matching: watch::Sender<(Arc<WatchList>, MatchOptions)>,
+    source: Box<dyn ProcessSource>,
+    steam: SteamLibrary,
+    /// The bundled emulator definitions with the user's overrides.
+    emulators: Arc<Emulators>,
+    /// Games from the Lutris, Heroic and Bottles libraries, while enabled.
+    libraries: Option<LauncherLibraries>,
+    pacer: Pacer,
+    power: PowerProbe,
+    next_scan: Instant,
+    commands: mpsc::UnboundedReceiver<Command>,
+    /// The games list refresh task, which only runs while the scanner is enabled.
+    list_refresh: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl Scanner {
+    pub fn new(
+        host: Box<dyn Host>,
+        paths: ScannerPaths,
+        handle: ScannerState,
+        inbox: ScannerInbox,
+        source: Box<dyn ProcessSource>,
+    ) -> Scanner {
+        let ScannerInbox { commands, settings } = inbox;
+        let (matching, _) = watch::channel((Arc::new(WatchList::default()), settings.match_options()));
+        Scanner {
+            host,
+            paths,
+            handle,
+            settings,
+            fetched_games: Vec::new(),
+            custom_games: Vec::new(),
+            watch_list: Arc::new(WatchList::default()),
+            detector: Detector::default(),
+            manual_activity: None,
+            status: StatusTracker::default(),
+            metrics: ScanMetrics::default(),
+            app_focused: false,
+            process_events: None,
+            matching,
+            source,
+            steam: SteamLibrary::discover(),
+            emulators: Arc::default(),
+            libraries: None,
+            pacer: Pacer::default(),
+            power: PowerProbe::default(),
+            next_scan: Instant::now(),
+            commands,
+            list_refresh: None,
+        }
+    }
+
+    /// Loads the custom games, emulators and the cached games list, and starts the
+    /// process event listener and the list refresh as configured.
+    pub fn load(&mut self) {
+        if let Some(path) = &self.paths.custom_games {
+            self.custom_games = custom::load(path);
+        }
+        self.emulators = Arc::new(Emulators::load(self.paths.emulators.as_deref()));
+
+        // Start scanning from the cached list right away; the refresh replaces it
+        // in the background once it succeeds.
+        let cached = self.cached_games();
+        if let Some(cache) = &cached {
+            log::info!("Loaded {} detectable games from cache", cache.games.len());
+            self.fetched_games = cache.games.clone();
+        }
+        self.apply_launcher_libraries();
+        self.rebuild_watch_list();
+        self.apply_process_events();
+        self.start_list_refresh(cached);
+    }
+
+    /// Ends all activity and starts over from the files on disk, as at launch.
+    pub fn restart(&mut self) {
+        log::info!("Restarting");
+        self.stop();
+        self.status = StatusTracker::default();
+        self.metrics = ScanMetrics::default();
+        self.pacer = Pacer::default();
+        self.load();
+        self.wake();
+    }
+
+    /// Reports every running game, including a manual activity, as stopped and
+    /// stops the background work.
+    fn stop(&mut self) {
+        if let Some(manual) = self.manual_activity.take() {
+            self.emit("game-activity", GameActivity::stopped(&manual));
+        }
+        let events = self.detector.stop_all(unix_now());
+        self.report(&events);
+        self.stop_list_refresh();
+        self.process_events = None;
+        self.libraries = None;
+    }
+
+    /// Starts refreshing the games list, beginning from `cached`, unless the
+    /// scanner is disabled or a refresh task is already running.
+    fn start_list_refresh(&mut self, cached: Option<GamesCache>) {
+        if !self.settings.enabled || self.list_refresh.is_some() {
+            return;
+        }
+        self.list_refresh = Some(self.host.refresh_games_list(self.handle.clone(), cached));
+    }
+
+    /// The cached games list for the configured source, if there is one.
+    fn cached_games(&self) -> Option<GamesCache> {
+        super::cached_games(self.paths.cache.as_deref(), &self.settings.games_source)
+    }
+
+    fn stop_list_refresh(&mut self) {
+        if let Some(task) = self.list_refresh.take() {
+            task.abort();
+            self.update_status(|status| status.fetching_list = false);
+        }
+    }
+
+    /// Handles commands and scans on the configured interval until every handle is dropped.
+    pub async fn run(mut self) {
+        loop {
+            let command = if self.settings.enabled {
+                match tokio::time::timeout_at(self.next_scan, self.commands.recv()).await {
+                    Ok(command) => command,
+                    Err(_) => {
+                        self.scan();
+                        continue;
+                    }
+                }
+            } else {
+                // If disabled, wait for a command (e.g. enable)
+                self.update_status(|status| status.disabled = true);
+                self.commands.recv().await
+            };
+
+            match command {
+                Some(Command::SetEnabled(enabled)) => self.set_enabled(enabled),
+                Some(Command::Rescan(reply)) => {
+                    let result = if self.settings.enabled {
+                        self.scan();
+                        Ok(self.running_games())
+                    } else {
+                        Err("game scanner is disabled".to_string())
+                    };
+                    let _ = reply.send(result);
+                }
+                Some(Command::Configure(change)) => change(&mut self),
+                Some(Command::Query(read)) => read(&self),
+                Some(Command::Wake) => self.wake(),
+                Some(Command::Shutdown(reply)) => {
+                    log::info!("Shutting down");
+                    self.stop();
+                    let _ = reply.send(());
+                    break;
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if self.settings.enabled != enabled {
+            log::info!("Scanner state set to: {}", enabled);
+        }
+        // A failed save is reported through the status.
+        let _ = self.update_settings(|settings| settings.enabled = enabled);
+        if enabled {
+            let cached = self.cached_games();
+            self.start_list_refresh(cached);
+        } else {
+            // Games aren't watched while disabled, so their sessions end now
+            // rather than whenever the scanner is enabled again.
+            let events = self.detector.stop_all(unix_now());
+            self.report(&events);
+            self.stop_list_refresh();
+        }
+        self.wake();
+    }
+
+    /// Makes the next scan happen right away.
+    pub fn wake(&mut self) {
+        self.next_scan = Instant::now();
+    }
+
+    /// Applies `change` to the settings and persists them.
+    pub fn update_settings(&mut self, change: impl FnOnce(&mut ScannerSettings)) -> Result<(), String> {
+        change(&mut self.settings);
+        self.publish_matching();
+        let saved = match &self.paths.settings {
+            Some(path) => self.settings.save(path).map_err(|e| format!("failed to save settings: {}", e)),
+            None => Ok(()),
+        };
+        let error = saved.as_ref().err().map(|message| save_error(message.clone()));
+        self.update_status(|status| status.settings_error = error);
+        saved
+    }
+
+    /// Applies `change` to the custom games, persists them and rebuilds the watch list.
+    pub fn update_custom_games(&mut self, change: impl FnOnce(&mut Vec<DetectableGame>)) -> Result<(), String> {
+        change(&mut self.custom_games);
+        let saved = match &self.paths.custom_games {
+            Some(path) => custom::save(path, &self.custom_games).map_err(|e| e.to_string()),
+            None => Ok(()),
+        };
+        self.rebuild_watch_list();
+        self.wake();
+        saved
+    }
+
+    /// Applies `change` to the status tracker and emits `scanner-status` if the
+    /// settled status changed, so scans alone don't emit anything.
+    pub fn update_status(&mut self, change: impl FnOnce(&mut StatusTracker)) {
+        let before = self.status.settled();
+        change(&mut self.status);
+        let after = self.status.settled();
+        if before != after {
+            if after != ScannerStatus::Idle {
+                log::info!("Status: {:?}", after);
+            }
+            self.emit("scanner-status", after);
+        }
+    }
+
+    /// Persists a freshly loaded list to the disk cache, makes it the watch list
+    /// and tells the frontend.
+    pub fn apply_games_list(&mut self, fresh: GamesCache) {
+        if let Some(path) = &self.paths.cache {
+            let error = fresh
+                .save(path)
+                .err()
+                .map(|e| save_error(format!("failed to write games cache: {}", e)));
+            self.update_status(|status| status.cache_error = error);
+        }
+        let update = GamesListUpdate {
+            count: fresh.games.len(),
+            source: fresh.source,
+        };
+        self.fetched_games = fresh.games;
+        self.rebuild_watch_list();
+        self.wake();
+        self.emit("games-list-updated", update);
+    }
+
+    /// Recomputes the watch list from the fetched, custom and launcher library games.
+    pub fn rebuild_watch_list(&mut self) {
+        let mut watch_list = custom::merge(&self.custom_games, &self.fetched_games);
+        if let Some(libraries) = &self.libraries {
+            watch_list.extend(libraries.games().iter().cloned());
+        }
+        #[cfg(debug_assertions)]
+        let watch_list = [watch_list, super::debug_games()].concat();
+        self.watch_list = Arc::new(WatchList::new(watch_list).with_emulators(self.emulators.clone()));
+        self.publish_matching();
+    }
+
+    fn publish_matching(&self) {
+        self.matching
+            .send_replace((self.watch_list.clone(), self.settings.match_options()));
+    }
+
+    /// Starts or stops the process event listener to match the settings.
+    /// Returns whether it is running.
+    pub fn apply_process_events(&mut self) -> bool {
+        if !self.settings.process_events {
+            self.process_events = None;
+        } else if self.process_events.is_none() {
+            match proc_events::Listener::start(self.matching.subscribe(), self.handle.clone()) {
+                Ok(listener) => {
+                    log::info!("Listening for process events");
+                    self.process_events = Some(listener);
+                }
+                Err(e) => log::warn!("Process events unavailable, polling only: {}", e),
+            }
+        }
+        self.process_events.is_some()
+    }
+
+    /// Starts or stops reading the launcher libraries to match the settings.
+    /// The watch list needs rebuilding afterwards.
+    pub fn apply_launcher_libraries(&mut self) {
+        if !self.settings.launcher_libraries {
+            self.libraries = None;
+        } else if self.libraries.is_none() {
+            let mut libraries = LauncherLibraries::new(LibraryPaths::discover());
+            libraries.refresh();
+            self.libraries = Some(libraries);
+        }
+    }
+
+    /// Every running game along with the current primary activity.
+    pub fn running_games(&self) -> GameActivitySet {
+        GameActivitySet::new(self.detector.activities(), self.primary())
+    }
+
+    /// The reported primary activity: the manual one if set, otherwise the detected one.
+    pub fn primary(&self) -> Option<&RunningGame> {
+        self.manual_activity.as_ref().or_else(|| self.detector.primary())
+    }
+
+    /// Sets or clears the manual activity and tells the frontend.
+    pub fn set_manual_activity(&mut self, manual: Option<RunningGame>) {
+        let previous = std::mem::replace(&mut self.manual_activity, manual);
+        let activity = match (&self.manual_activity, previous) {
+            (Some(game), _) => {
+                log::info!("Manual activity: {}", game.name);
+                GameActivity::started(game)
+            }
+            (None, Some(previous)) => {
+                log::info!("Cleared manual activity: {}", previous.name);
+                self.detector
+                    .primary()
+                    .map(GameActivity::started)
+                    .unwrap_or_else(|| GameActivity::stopped(&previous))
+            }
+            (None, None) => return,
+        };
+        self.emit("game-activity", activity);
+        self.emit("game-activity-set", self.running_games());
+    }
+
+    /// Scans the running processes, reports what changed and schedules the next scan.
+    fn scan(&mut self) {
+        self.update_status(|status| {
+            status.disabled = false;
+            status.scanning = true;
+        });
+        let refresh_started = std::time::Instant::now();
+        let snapshot = self.source.snapshot();
+        let refresh_time = refresh_started.elapsed();
+
+        if self.libraries.as_mut().map_or(false, LauncherLibraries::refresh) {
+            self.rebuild_watch_list();
+        }
+        let config = self.settings.scan_config();
+        let steam = self.steam.resolve(&snapshot, &self.watch_list);
+        let events = self
+            .detector
+            .scan(&self.watch_list, &steam, &snapshot, unix_now(), &config);
+        self.report(&events);
+        self.update_status(|status| status.scanning = false);
+
+        let interval = self.settings.scan_interval;
+        let conditions = if interval.adaptive {
+            let (on_battery, idle_seconds) = self.power.read();
+            Conditions {
+                focused: self.app_focused,
+                on_battery,
+                idle_seconds,
+            }
+        } else {
+            Conditions::default()
+        };
+        let delay = self.pacer.next_delay(interval, !events.is_empty(), conditions);
+        self.metrics.record(refresh_time, delay);
+        self.next_scan = Instant::now() + delay;
+    }
+
+    /// Records the sessions that `events` finished to the history and tells the frontend.
+    fn report(&mut self, events: &[ScanEvent]) {
+        let recorded = match &self.paths.history {
+            Some(path) if self.settings.record_history => history::record(path, events),
+            _ => None,
+        };
+        self.emit_scan_events(events);
+        self.update_status(|status| match recorded {
+            Some(Ok(())) => status.history_error = None,
+            None => {}
+            Some(Err(e)) => {
+                status.history_error = Some(ScannerError {
+                    kind: ErrorKind::History,
+                    message: format!("failed to record session: {}", e),
+                    retry_at: None,
+                })
+            }
+        });
+    }
+
+    /// Forwards the changes found by a scan to the frontend. While a manual
+    /// activity is set it stays the primary one, so primary changes aren't reported.
+    fn emit_scan_events(&self, events: &[ScanEvent]) {
+        for event in events {
+            if self.manual_activity.is_some() && matches!(event, ScanEvent::PrimaryChanged { .. }) {
+                continue;
+            }
+            match event {
+                ScanEvent::Stopped { game, .. } => {
+                    log::info!("Stopped: {}", game.name);
+                    if !game.hidden {
+                        self.emit("game-activity-removed", GameActivity::stopped(game));
+                    }
+                }
+                ScanEvent::Started(game) => {
+                    log::info!("Detected: {}", game.name);
+                    if !game.hidden {
+                        self.emit("game-activity-added", GameActivity::started(game));
+                    }
+                }
+                ScanEvent::Hidden(game) => {
+                    self.emit("game-activity-removed", GameActivity::stopped(game));
+                }
+                ScanEvent::Shown(game) => {
+                    self.emit("game-activity-added", GameActivity::started(game));
+                }
+                ScanEvent::PrimaryChanged { current: Some(game), .. } => {
+                    // Game started, or the primary switched to another running game
+                    log::info!("Primary activity: {}", game.name);
+                    self.emit("game-activity", GameActivity::started(game));
+                }
+                ScanEvent::PrimaryChanged { previous: Some(prev), current: None } => {
+                    self.emit("game-activity", GameActivity::stopped(prev));
+                }
+                ScanEvent::PrimaryChanged { .. } => {}
+            }
+        }
+        if !events.is_empty() {
+            self.emit("game-activity-set", self.running_games());
+        }
+    }
+
+    fn emit(&self, event: &str, payload: impl Serialize) {
+        if let Ok(payload) = serde_json::to_value(payload) {
+            self.host.emit(event, payload);
+        }
+    }
+}
+
+fn save_error(message: String) -> ScannerError {
+    ScannerError {
+        kind: ErrorKind::Save,
+        message,
+        retry_at: None,
+    }
+}
+
+impl Drop for Scanner {
+    fn drop(&mut self) {
+        // The refresh task would outlive a panicking scanner and keep feeding
+        // its replacement alongside the replacement's own refresh.
+        if let Some(task) = self.list_refresh.take() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::game_scanner::cache::CACHE_FILE_NAME;
+    use crate::game_scanner::custom::CUSTOM_GAMES_FILE_NAME;
+    use crate::game_scanner::history::HISTORY_FILE_NAME;
+    use crate::game_scanner::hysteresis::Hysteresis;
+    use crate::game_scanner::matcher::CURRENT_OS;
+    use crate::game_scanner::process::{ProcessInfo, SharedSource};
+    use crate::game_scanner::settings::SETTINGS_FILE_NAME;
+    use crate::game_scanner::source::GamesSource;
+    use crate::game_scanner::test_support::temp_dir;
+    use crate::game_scanner::GameExecutable;
+
+    /// Records what the scanner tells the frontend and the list refreshes it starts.
+    #[derive(Clone, Default)]
+    struct TestHost {
+        events: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+        refreshes: Arc<AtomicUsize>,
+    }
+
+    impl Host for TestHost {
+        fn emit(&self, event: &str, payload: serde_json::Value) {
+            self.events.lock().unwrap().push((event.to_string(), payload));
+        }
+
+        fn refresh_games_list(&self, _: ScannerState, _: Option<GamesCache>) -> JoinHandle<Result<(), String>> {
+            self.refreshes.fetch_add(1, Ordering::SeqCst);
+            tauri::async_runtime::spawn(std::future::pending())
+        }
+    }
+
+    impl TestHost {
+        /// The `game-activity` events so far, as (id, is_running).
+        fn activity(&self) -> Vec<(String, bool)> {
+            self.events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(event, _)| event == "game-activity")
+                .map(|(_, payload)| (payload["id"].as_str().unwrap().to_string(), payload["is_running"].as_bool().unwrap()))
+                .collect()
+        }
+    }
+
+    /// A scanner task watching for the game `hades`, with its files in a temp dir.
+    struct TestScanner {
+        state: ScannerState,
+        host: TestHost,
+        processes: SharedSource,
+        paths: ScannerPaths,
+    }
+
+    fn settings() -> ScannerSettings {
+        ScannerSettings {
+            enabled: true,
+            hysteresis: Hysteresis {
+                start_scans: 1,
+                stop_scans: 1,
+            },
+            ..ScannerSettings::default()
+        }
+    }
+
+    fn start(dir: &Path, settings: ScannerSettings) -> TestScanner {
+        let paths = ScannerPaths {
+            settings: Some(dir.join(SETTINGS_FILE_NAME)),
+            custom_games: Some(dir.join(CUSTOM_GAMES_FILE_NAME)),
+            emulators: None,
+            cache: Some(dir.join(CACHE_FILE_NAME)),
+            history: Some(dir.join(HISTORY_FILE_NAME)),
+        };
+        let hades = DetectableGame {
+            id: "hades".to_string(),
+            name: "Hades".to_string(),
+            executables: Some(vec![GameExecutable {
+                os: CURRENT_OS.to_string(),
+                name: "hades".to_string(),
+                ..GameExecutable::default()
+            }]),
+            ..DetectableGame::default()
+        };
+        let cache = GamesCache {
+            source: GamesSource::default().to_string(),
+            games: vec![hades],
+            ..GamesCache::default()
+        };
+        cache.save(paths.cache.as_ref().unwrap()).unwrap();
+
+        let host = TestHost::default();
+        let processes = SharedSource::default();
+        let (state, inbox) = ScannerState::new(settings);
+        spawn_scanner(&host, &processes, &paths, &state, inbox);
+        TestScanner {
+            state,
+            host,
+            processes,
+            paths,
+        }
+    }
+
+    fn spawn_scanner(host: &TestHost, processes: &SharedSource, paths: &ScannerPaths, state: &ScannerState, inbox: ScannerInbox) {
+        let mut scanner = Scanner::new(Box::new(host.clone()), paths.clone(), state.clone(), inbox, Box::new(processes.clone()));
+        scanner.load();
+        state.set_task(tauri::async_runtime::spawn(scanner.run()));
+    }
+
+    fn hades() -> ProcessInfo {
+        ProcessInfo {
+            pid: 42,
+            name: "hades".to_string(),
+            ..ProcessInfo::default()
+        }
+    }
+
+    fn ids(set: &GameActivitySet) -> Vec<&str> {
+        set.games.iter().map(|game| game.id.as_str()).collect()
+    }
+
+    #[test]
+    fn rescans_report_started_and_stopped_games() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-rescan");
+            let scanner = start(&dir, settings());
+            scanner.processes.set(vec![hades()]);
+            let running = scanner.state.rescan().await.unwrap();
+            assert_eq!(ids(&running), vec!["hades"]);
+            assert_eq!(running.primary.unwrap().id, "hades");
+
+            scanner.processes.set(Vec::new());
+            let running = scanner.state.rescan().await.unwrap();
+            assert!(running.games.is_empty() && running.primary.is_none());
+            assert_eq!(scanner.host.activity(), vec![("hades".to_string(), true), ("hades".to_string(), false)]);
+        });
+    }
+
+    #[test]
+    fn disabling_ends_sessions_and_enabling_resumes() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-enable");
+            let scanner = start(&dir, settings());
+            scanner.processes.set(vec![hades()]);
+            scanner.state.rescan().await.unwrap();
+
+            scanner.state.set_enabled(false).unwrap();
+            assert_eq!(scanner.state.rescan().await.unwrap_err(), "game scanner is disabled");
+            assert_eq!(scanner.host.activity().last(), Some(&("hades".to_string(), false)));
+            assert_eq!(history::load(scanner.paths.history.as_ref().unwrap()).len(), 1);
+            assert!(!ScannerSettings::load(scanner.paths.settings.as_ref().unwrap()).enabled);
+            let status = scanner.state.query(|scanner| scanner.status.status()).await.unwrap();
+            assert_eq!(status, ScannerStatus::Disabled);
+
+            scanner.state.set_enabled(true).unwrap();
+            assert_eq!(ids(&scanner.state.rescan().await.unwrap()), vec!["hades"]);
+        });
+    }
+
+    #[test]
+    fn configure_changes_apply_to_the_next_scan() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-configure");
+            let scanner = start(&dir, settings());
+            scanner
+                .state
+                .configure(|scanner| {
+                    scanner.update_settings(|settings| {
+                        settings.ignored.games.insert("hades".to_string());
+                    })
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            scanner.processes.set(vec![hades()]);
+
+            // Ignored games are tracked, but not reported.
+            let running = scanner.state.rescan().await.unwrap();
+            assert!(running.games.is_empty() && running.primary.is_none());
+            assert!(scanner.host.activity().is_empty());
+            let saved = ScannerSettings::load(scanner.paths.settings.as_ref().unwrap());
+            assert!(saved.ignored.games.contains("hades"));
+        });
+    }
+
+    #[test]
+    fn manual_activity_overrides_the_detected_one() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-manual");
+            let scanner = start(&dir, settings());
+            scanner.processes.set(vec![hades()]);
+            scanner.state.rescan().await.unwrap();
+
+            let manual = RunningGame {
+                id: "manual:reading".to_string(),
+                name: "Reading".to_string(),
+                ..RunningGame::default()
+            };
+            scanner.state.update(move |scanner| scanner.set_manual_activity(Some(manual))).unwrap();
+            // The detected game stopping doesn't replace the manual activity.
+            scanner.processes.set(Vec::new());
+            let running = scanner.state.rescan().await.unwrap();
+            assert_eq!(running.primary.unwrap().id, "manual:reading");
+
+            scanner.state.update(|scanner| scanner.set_manual_activity(None)).unwrap();
+            scanner.state.query(|_| ()).await.unwrap();
+            assert_eq!(
+                scanner.host.activity(),
+                vec![
+                    ("hades".to_string(), true),
+                    ("manual:reading".to_string(), true),
+                    ("manual:reading".to_string(), false),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn a_crashed_task_can_be_replaced() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-crash");
+            let scanner = start(&dir, settings());
+            assert!(!scanner.state.is_finished());
+
+            scanner.state.update(|_| panic!("scanner bug")).unwrap();
+            assert_eq!(scanner.state.query(|_| ()).await.unwrap_err(), STOPPED);
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !scanner.state.is_finished() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+
+            let inbox = scanner.state.reopen(settings());
+            spawn_scanner(&scanner.host, &scanner.processes, &scanner.paths, &scanner.state, inbox);
+            scanner.processes.set(vec![hades()]);
+            assert_eq!(ids(&scanner.state.rescan().await.unwrap()), vec!["hades"]);
+        });
+    }
+
+    #[test]
+    fn shutdown_reports_running_games_as_stopped() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-shutdown");
+            let scanner = start(&dir, settings());
+            scanner.processes.set(vec![hades()]);
+            scanner.state.rescan().await.unwrap();
+
+            scanner.state.shutdown().await.unwrap();
+            assert_eq!(scanner.host.activity(), vec![("hades".to_string(), true), ("hades".to_string(), false)]);
+            let sessions = history::load(scanner.paths.history.as_ref().unwrap());
+            assert_eq!(sessions.len(), 1);
+            assert_eq!(sessions[0].game_id, "hades");
+            assert_eq!(scanner.state.rescan().await.unwrap_err(), STOPPED);
+        });
+    }
+
+    #[test]
+    fn restart_ends_activity_and_starts_over() {
+        tauri::async_runtime::block_on(async {
+            let dir = temp_dir("actor-restart");
+            let scanner = start(&dir, settings());
+            scanner.processes.set(vec![hades()]);
+            scanner.state.rescan().await.unwrap();
+            assert_eq!(scanner.host.refreshes.load(Ordering::SeqCst), 1);
+
+            // Nothing runs for the scan the restart starts with, so only the stop is reported.
+            scanner.processes.set(Vec::new());
+            scanner.state.configure(Scanner::restart).await.unwrap();
+            assert_eq!(scanner.host.activity(), vec![("hades".to_string(), true), ("hades".to_string(), false)]);
+            assert_eq!(scanner.host.refreshes.load(Ordering::SeqCst), 2);
+
+            scanner.processes.set(vec![hades()]);
+            assert_eq!(ids(&scanner.state.rescan().await.unwrap()), vec!["hades"]);
+        });
+    }
+
+    #[test]
+    fn the_list_is_not_fetched_while_disabled() {
+        tauri::async_runtime::block_on(async {
+            let disabled = ScannerSettings {
+                enabled: false,
+                ..settings()
+            };
+            let dir = temp_dir("actor-disabled");
+            let scanner = start(&dir, disabled);
+            let refreshes = || scanner.host.refreshes.load(Ordering::SeqCst);
+            scanner.state.configure(Scanner::restart).await.unwrap();
+            assert_eq!(refreshes(), 0);
+
+            scanner.state.set_enabled(true).unwrap();
+            scanner.state.query(|_| ()).await.unwrap();
+            assert_eq!(refreshes(), 1);
+
+            scanner.state.set_enabled(false).unwrap();
+            scanner.state.configure(Scanner::restart).await.unwrap();
+            assert_eq!(refreshes(), 1);
+        });
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::activity::{LauncherPolicy, PrimaryPolicy};
+use super::engine::ScanConfig;
+use super::hysteresis::Hysteresis;
+use super::matcher::MatchOptions;
+use super::pacing::ScanInterval;
+use super::privacy::IgnoreList;
+use super::source::GamesSource;
+use super::storage;
+
+/// File name of the scanner settings inside the app config directory.
+pub const SETTINGS_FILE_NAME: &str = "game_scanner.json";
+
+/// User-configurable scanner settings, persisted as JSON.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScannerSettings {
+    /// Whether the scanner runs at all.
+    pub enabled: bool,
+    pub games_source: GamesSource,
+    /// Match Windows executables run through Wine or Proton on Linux and macOS.
+    pub compatibility_layer: bool,
+    /// How the primary activity is picked when several games run at once.
+    pub primary_policy: PrimaryPolicy,
+    /// Id of the game preferred by [`PrimaryPolicy::Pinned`].
+    pub pinned_game: Option<String>,
+    /// Keep a local history of play sessions.
+    pub record_history: bool,
+    /// Scans needed to confirm that a game started or stopped.
+    pub hysteresis: Hysteresis,
+    /// Time between scans.
+    pub scan_interval: ScanInterval,
+    /// Wake the scanner on process start and exit events where the OS allows it.
+    pub process_events: bool,
+    /// Games and categories kept out of shared activity.
+    pub ignored: IgnoreList,
+    /// Hours between refreshes of the detectable games list; 0 refreshes only at startup.
+    pub list_refresh_hours: u64,
+    /// How games detected through a launcher are treated.
+    pub launcher_policy: LauncherPolicy,
+    /// Also watch for games installed through Lutris, Heroic and Bottles.
+    pub launcher_libraries: bool,
+}
+
+impl Default for ScannerSettings {
+    fn default() -> Self {
+        ScannerSettings {
+            enabled: false,
+            games_source: GamesSource::default(),
+            compatibility_layer: false,
+            primary_policy: PrimaryPolicy::default(),
+            pinned_game: None,
+            record_history: true,
+            hysteresis: Hysteresis::default(),
+            scan_interval: ScanInterval::default(),
+            process_events: false,
+            ignored: IgnoreList::default(),
+            list_refresh_hours: 24,
+            launcher_policy: LauncherPolicy::default(),
+            launcher_libraries: false,
+        }
+    }
+}
+
+impl ScannerSettings {
+    pub fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            compatibility_layer: self.compatibility_layer,
+        }
+    }
+
+    /// The settings the detector needs for one scan.
+    pub fn scan_config(&self) -> ScanConfig {
+        ScanConfig {
+            options: self.match_options(),
+            hysteresis: self.hysteresis,
+            policy: self.primary_policy,
+            pinned: self.pinned_game.clone(),
+            ignored: self.ignored.clone(),
+            launchers: self.launcher_policy,
+        }
+    }
+
+    /// Reads settings from `path`, falling back to defaults if the file is
+    /// missing or invalid. An invalid file is set aside.
+    pub fn load(path: &Path) -> ScannerSettings {
+        match fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                storage::set_aside(path, e);
+                ScannerSettings::default()
+            }),
+            Err(_) => ScannerSettings::default(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::write_json_atomic(path, self)
+    }
+}
+
+/// Location of the settings file for `app`, if the config directory is known.
+pub fn settings_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path_resolver()
+        .app_config_dir()
+        .map(|dir| dir.join(SETTINGS_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::test_support::temp_dir;
+
+    #[test]
+    fn missing_settings_load_their_defaults() {
+        let dir = temp_dir("settings");
+        let path = dir.join(SETTINGS_FILE_NAME);
+        fs::write(&path, br#"{"enabled":true}"#).unwrap();
+        let loaded = ScannerSettings::load(&path);
+        assert!(loaded.enabled && loaded.record_history);
+    }
+
+    #[test]
+    fn invalid_settings_are_set_aside() {
+        let dir = temp_dir("settings-bad");
+        let path = dir.join(SETTINGS_FILE_NAME);
+        fs::write(&path, br#"{"enabled":tru"#).unwrap();
+        assert!(!ScannerSettings::load(&path).enabled);
+        assert!(!path.exists());
+        assert_eq!(fs::read(path.with_extension("json.bad")).unwrap(), br#"{"enabled":tru"#);
+    }
+}
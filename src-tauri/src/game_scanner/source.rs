@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::status::ErrorKind;
+use super::DetectableGame;
+
+/// Discord's public list of detectable applications.
+pub const DETECTABLE_GAMES_URL: &str = "https://discord.com/api/v9/applications/detectable";
+
+/// Where the detectable games list is loaded from.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GamesSource {
+    /// An HTTP(S) endpoint serving the list. `file://` URLs are read from disk.
+    Url { url: String },
+    /// A JSON file on the local filesystem.
+    File { path: PathBuf },
+    /// A JSON file bundled with the app as a Tauri resource.
+    Bundled { resource: String },
+}
+
+impl Default for GamesSource {
+    fn default() -> Self {
+        GamesSource::Url {
+            url: DETECTABLE_GAMES_URL.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GamesSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GamesSource::Url { url } => write!(f, "{}", url),
+            GamesSource::File { path } => write!(f, "{}", path.display()),
+            GamesSource::Bundled { resource } => write!(f, "resource:{}", resource),
+        }
+    }
+}
+
+/// A [`GamesSource`] resolved to something that can actually be read.
+pub enum SourceLocation {
+    Remote(String),
+    Local(PathBuf),
+}
+
+impl GamesSource {
+    /// Resolves file URLs and bundled resources to paths on disk.
+    pub fn locate(&self, app: &AppHandle) -> Result<SourceLocation, SourceError> {
+        match self {
+            GamesSource::Url { url } if url.starts_with("file://") => reqwest::Url::parse(url)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .map(SourceLocation::Local)
+                .ok_or_else(|| SourceError::Invalid(format!("'{}' is not a valid file URL", url))),
+            GamesSource::Url { url } => Ok(SourceLocation::Remote(url.clone())),
+            GamesSource::File { path } => Ok(SourceLocation::Local(path.clone())),
+            GamesSource::Bundled { resource } => app
+                .path_resolver()
+                .resolve_resource(resource)
+                .map(SourceLocation::Local)
+                .ok_or_else(|| SourceError::Invalid(format!("bundled resource '{}' not found", resource))),
+        }
+    }
+}
+
+/// Errors raised while loading a detectable games list.
+#[derive(Debug)]
+pub enum SourceError {
+    /// The remote source could not be reached or returned an error status.
+    Network(reqwest::Error),
+    /// A local source could not be read.
+    Io(PathBuf, io::Error),
+    /// The payload is not a valid detectable games list.
+    Parse(serde_json::Error),
+    /// The source or its contents are unusable.
+    Invalid(String),
+}
+
+impl SourceError {
+    /// Whether trying again later might succeed. Malformed payloads and bad
+    /// configuration won't fix themselves, so those are reported instead.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SourceError::Network(_))
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            SourceError::Network(_) => ErrorKind::Network,
+            SourceError::Io(..) => ErrorKind::Io,
+            SourceError::Parse(_) | SourceError::Invalid(_) => ErrorKind::InvalidList,
+        }
+    }
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Network(e) => write!(f, "network error: {}", e),
+            SourceError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            SourceError::Parse(e) => write!(f, "invalid games list: {}", e),
+            SourceError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<reqwest::Error> for SourceError {
+    fn from(e: reqwest::Error) -> Self {
+        SourceError::Network(e)
+    }
+}
+
+/// Parses and validates a detectable games payload. Entries that don't parse,
+/// lack an id or name, or repeat an earlier id are skipped, so one bad row
+/// doesn't cost the whole list. The payload is only rejected if it isn't a
+/// list, or if it has entries and none of them are usable.
+pub fn parse_games(data: &[u8]) -> Result<Vec<DetectableGame>, SourceError> {
+    let entries: Vec<serde_json::Value> = serde_json::from_slice(data).map_err(SourceError::Parse)?;
+
+    let mut games: Vec<DetectableGame> = Vec::with_capacity(entries.len());
+    let mut ids = HashSet::new();
+    let mut first_error = None;
+    for (index, entry) in entries.into_iter().enumerate() {
+        let error = match serde_json::from_value::<DetectableGame>(entry) {
+            Err(e) => SourceError::Parse(e),
+            Ok(game) if game.id.trim().is_empty() => SourceError::Invalid(format!("entry {} has an empty id", index)),
+            Ok(game) if game.name.trim().is_empty() => {
+                SourceError::Invalid(format!("entry {} ('{}') has an empty name", index, game.id))
+            }
+            Ok(game) if !ids.insert(game.id.clone()) => {
+                SourceError::Invalid(format!("entry {} duplicates id '{}'", index, game.id))
+            }
+            Ok(game) => {
+                games.push(game);
+                continue;
+            }
+        };
+        log::warn!("Skipping detectable games entry {}: {}", index, error);
+        first_error.get_or_insert(error);
+    }
+
+    match first_error {
+        Some(error) if games.is_empty() => Err(error),
+        _ => Ok(games),
+    }
+}
+
+/// Reads and validates a detectable games list from a local JSON file.
+pub fn read_games_file(path: &Path) -> Result<Vec<DetectableGame>, SourceError> {
+    let data = fs::read(path).map_err(|e| SourceError::Io(path.to_path_buf(), e))?;
+    parse_games(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_full_detectable_schema() {
+        let games = parse_games(
+            br#"[{
+                "id": "1", "name": "Counter-Strike", "aliases": ["CS", "Counter Strike"],
+                "icon_hash": "abc", "themes": ["Shooter"], "hook": true,
+                "executables": [
+                    {"os": "linux", "name": "hl2_linux", "arguments": "-game cstrike", "is_launcher": false},
+                    {"os": "win32", "name": "steam.exe", "is_launcher": true}
+                ]
+            }, {"id": "2", "name": "Minimal"}]"#,
+        )
+        .unwrap();
+
+        let cs = &games[0];
+        assert_eq!(cs.aliases, vec!["CS", "Counter Strike"]);
+        assert_eq!(cs.icon_hash.as_deref(), Some("abc"));
+        let exes = cs.executables.as_ref().unwrap();
+        assert_eq!(exes[0].arguments.as_deref(), Some("-game cstrike"));
+        assert!(!exes[0].is_launcher && exes[1].is_launcher);
+        assert!(games[1].aliases.is_empty() && games[1].icon_hash.is_none());
+    }
+
+    #[test]
+    fn null_lists_read_as_empty() {
+        let games = parse_games(br#"[{"id": "1", "name": "Game", "aliases": null, "themes": null, "third_party_skus": null}]"#)
+            .unwrap();
+        assert!(games[0].aliases.is_empty() && games[0].themes.is_empty() && games[0].third_party_skus.is_empty());
+    }
+
+    #[test]
+    fn bad_entries_are_skipped() {
+        let games = parse_games(
+            br#"[
+                {"id": "1", "name": "First"},
+                {"id": "", "name": "No id"},
+                {"id": "2", "name": " "},
+                {"id": "1", "name": "Duplicate"},
+                {"id": "3", "name": 3},
+                {"id": "4", "name": "Last"}
+            ]"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = games.iter().map(|game| game.name.as_str()).collect();
+        assert_eq!(names, vec!["First", "Last"]);
+
+        assert!(parse_games(b"[]").unwrap().is_empty());
+        assert!(matches!(parse_games(br#"[{"id": "", "name": "No id"}]"#), Err(SourceError::Invalid(_))));
+        assert!(matches!(parse_games(br#"{"id": "1"}"#), Err(SourceError::Parse(_))));
+    }
+}
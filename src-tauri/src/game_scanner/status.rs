@@ -0,0 +1,159 @@
+//! What the scanner is doing, for display in the settings page.
+//!
+//! The scan loop and the list refresh each update their part of a
+//! [`StatusTracker`]; [`StatusTracker::status`] folds those into the single
+//! [`ScannerStatus`] reported to the frontend. Errors outrank activity, so a
+//! failing list refresh stays visible while scans carry on from the cache.
+
+use serde::Serialize;
+
+/// What went wrong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The games list couldn't be downloaded.
+    Network,
+    /// A games list file couldn't be read.
+    Io,
+    /// The games list was malformed.
+    InvalidList,
+    /// A play session couldn't be written to the history.
+    History,
+    /// The scanner task stopped unexpectedly; `restart_game_scanner` starts a new one.
+    Crashed,
+    /// The settings or the games list cache couldn't be saved.
+    Save,
+}
+
+/// An error the scanner is currently affected by.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScannerError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Unix timestamp (seconds) of the next automatic retry, if one is scheduled.
+    pub retry_at: Option<u64>,
+}
+
+/// The scanner status as reported by `get_scanner_status` and the
+/// `scanner-status` event.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ScannerStatus {
+    Idle,
+    FetchingList,
+    Scanning,
+    Disabled,
+    Error {
+        kind: ErrorKind,
+        message: String,
+        retry_at: Option<u64>,
+    },
+}
+
+/// The parts of the scanner that contribute to its status.
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+    pub disabled: bool,
+    pub fetching_list: bool,
+    pub scanning: bool,
+    /// The last list refresh failed.
+    pub list_error: Option<ScannerError>,
+    /// The last session couldn't be recorded.
+    pub history_error: Option<ScannerError>,
+    /// The settings couldn't be saved the last time they changed.
+    pub settings_error: Option<ScannerError>,
+    /// The last games list couldn't be written to the cache.
+    pub cache_error: Option<ScannerError>,
+}
+
+impl StatusTracker {
+    pub fn status(&self) -> ScannerStatus {
+        self.fold(self.scanning)
+    }
+
+    /// The status apart from scanning, which starts and ends with every scan
+    /// and isn't worth an event each time.
+    pub fn settled(&self) -> ScannerStatus {
+        self.fold(false)
+    }
+
+    fn fold(&self, scanning: bool) -> ScannerStatus {
+        if self.disabled {
+            return ScannerStatus::Disabled;
+        }
+        let error = self
+            .list_error
+            .as_ref()
+            .or(self.history_error.as_ref())
+            .or(self.settings_error.as_ref())
+            .or(self.cache_error.as_ref());
+        if let Some(error) = error {
+            return ScannerStatus::Error {
+                kind: error.kind,
+                message: error.message.clone(),
+                retry_at: error.retry_at,
+            };
+        }
+        if self.fetching_list {
+            ScannerStatus::FetchingList
+        } else if scanning {
+            ScannerStatus::Scanning
+        } else {
+            ScannerStatus::Idle
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(kind: ErrorKind) -> Option<ScannerError> {
+        Some(ScannerError {
+            kind,
+            message: "failed".to_string(),
+            retry_at: Some(40),
+        })
+    }
+
+    #[test]
+    fn status_precedence() {
+        let mut tracker = StatusTracker::default();
+        assert_eq!(tracker.status(), ScannerStatus::Idle);
+
+        tracker.scanning = true;
+        assert_eq!(tracker.status(), ScannerStatus::Scanning);
+        assert_eq!(tracker.settled(), ScannerStatus::Idle);
+        tracker.fetching_list = true;
+        assert_eq!(tracker.status(), ScannerStatus::FetchingList);
+
+        tracker.history_error = error(ErrorKind::History);
+        tracker.list_error = error(ErrorKind::Network);
+        assert!(matches!(tracker.status(), ScannerStatus::Error { kind: ErrorKind::Network, .. }));
+        tracker.list_error = None;
+        assert!(matches!(tracker.status(), ScannerStatus::Error { kind: ErrorKind::History, .. }));
+        tracker.settings_error = error(ErrorKind::Save);
+        tracker.history_error = None;
+        assert!(matches!(tracker.settled(), ScannerStatus::Error { kind: ErrorKind::Save, .. }));
+
+        tracker.disabled = true;
+        assert_eq!(tracker.status(), ScannerStatus::Disabled);
+    }
+
+    #[test]
+    fn serializes_with_a_state_tag() {
+        let status = StatusTracker {
+            list_error: error(ErrorKind::Network),
+            ..StatusTracker::default()
+        }
+        .status();
+        assert_eq!(
+            serde_json::to_value(status).unwrap(),
+            serde_json::json!({"state": "error", "kind": "network", "message": "failed", "retry_at": 40})
+        );
+        assert_eq!(
+            serde_json::to_value(ScannerStatus::FetchingList).unwrap(),
+            serde_json::json!({"state": "fetching_list"})
+        );
+    }
+}
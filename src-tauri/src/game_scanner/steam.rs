@@ -0,0 +1,322 @@
+//! Games launched through Steam on Linux.
+//!
+//! Steam starts every game, native or Proton, under its `reaper` process with a
+//! command line like `reaper SteamLaunch AppId=620 -- ...`. The game's own
+//! executable often has a generic name that isn't on the detectable list, so
+//! the app id is resolved instead: to the watch list entry for that Steam app
+//! if there is one, otherwise to the name in the local `appmanifest_620.acf`
+//! found through Steam's `libraryfolders.vdf`.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::index::WatchList;
+use super::process::ProcessInfo;
+use super::vdf::{self, Vdf};
+use super::DetectableGame;
+
+/// Prefix of the ids of Steam games that aren't on the detectable list.
+pub const STEAM_ID_PREFIX: &str = "steam:";
+
+/// The Steam app id `process` was launched for, if it is Steam's launch wrapper.
+pub fn app_id(process: &ProcessInfo) -> Option<u32> {
+    if !process.cmd.iter().any(|arg| arg == "SteamLaunch") {
+        return None;
+    }
+    process.cmd.iter().find_map(|arg| {
+        let (key, value) = arg.split_once('=')?;
+        if key.eq_ignore_ascii_case("AppId") {
+            value.parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// The Steam games resolved for one snapshot, by app id.
+#[derive(Default)]
+pub struct SteamGames {
+    games: HashMap<u32, DetectableGame>,
+}
+
+impl SteamGames {
+    pub fn get(&self, app_id: u32) -> Option<&DetectableGame> {
+        self.games.get(&app_id)
+    }
+
+    #[cfg(test)]
+    pub fn insert(&mut self, app_id: u32, game: DetectableGame) {
+        self.games.insert(app_id, game);
+    }
+}
+
+/// The local Steam installations, for looking up the names of installed apps.
+pub struct SteamLibrary {
+    roots: Vec<PathBuf>,
+    names: HashMap<u32, String>,
+    /// The library folders of every root, as last read.
+    libraries: Vec<PathBuf>,
+    /// Apps found in none of `libraries`.
+    missing: HashSet<u32>,
+    /// Modification times of each root's `libraryfolders.vdf` and each
+    /// library's `steamapps`, which gains a manifest when an app is installed.
+    stamps: Vec<Option<SystemTime>>,
+}
+
+impl SteamLibrary {
+    pub fn new(roots: Vec<PathBuf>) -> SteamLibrary {
+        SteamLibrary {
+            roots,
+            names: HashMap::new(),
+            libraries: Vec::new(),
+            missing: HashSet::new(),
+            stamps: Vec::new(),
+        }
+    }
+
+    /// The Steam installations in the user's home directory.
+    pub fn discover() -> SteamLibrary {
+        SteamLibrary::new(default_roots())
+    }
+
+    /// Resolves the Steam games launched in `snapshot`. Apps that are on the
+    /// watch list resolve to their entry there; the rest need a local manifest.
+    pub fn resolve(&mut self, snapshot: &[ProcessInfo], watch_list: &WatchList) -> SteamGames {
+        let mut resolved = SteamGames::default();
+        for app_id in snapshot.iter().filter_map(app_id) {
+            if resolved.games.contains_key(&app_id) {
+                continue;
+            }
+            let game = match watch_list.steam_game(app_id) {
+                Some(game) => game.clone(),
+                None => match self.app_name(app_id) {
+                    Some(name) => DetectableGame {
+                        id: format!("{}{}", STEAM_ID_PREFIX, app_id),
+                        name,
+                        ..DetectableGame::default()
+                    },
+                    None => continue,
+                },
+            };
+            resolved.games.insert(app_id, game);
+        }
+        resolved
+    }
+
+    /// The name of installed app `app_id`. Names found are remembered, and so
+    /// are misses until the library folders or their contents change.
+    fn app_name(&mut self, app_id: u32) -> Option<String> {
+        if let Some(name) = self.names.get(&app_id) {
+            return Some(name.clone());
+        }
+        if self.stamps() != self.stamps {
+            self.libraries = self.roots.iter().flat_map(|root| library_folders(root)).collect();
+            self.missing.clear();
+            self.stamps = self.stamps();
+        }
+        if self.missing.contains(&app_id) {
+            return None;
+        }
+        match self.libraries.iter().find_map(|library| manifest_name(library, app_id)) {
+            Some(name) => {
+                self.names.insert(app_id, name.clone());
+                Some(name)
+            }
+            None => {
+                self.missing.insert(app_id);
+                None
+            }
+        }
+    }
+
+    fn stamps(&self) -> Vec<Option<SystemTime>> {
+        self.roots
+            .iter()
+            .map(|root| root.join("steamapps").join("libraryfolders.vdf"))
+            .chain(self.libraries.iter().map(|library| library.join("steamapps")))
+            .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn default_roots() -> Vec<PathBuf> {
+    let home = match std::env::var_os("HOME") {
+        Some(home) => PathBuf::from(home),
+        None => return Vec::new(),
+    };
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for root in [
+        ".steam/steam",
+        ".local/share/Steam",
+        ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    ] {
+        // `~/.steam/steam` usually links to one of the others.
+        if let Ok(root) = home.join(root).canonicalize() {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+    roots
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_roots() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// The library folders of the Steam installation at `root`, starting with
+/// `root` itself. Reads both the current and the pre-2021 layout of
+/// `libraryfolders.vdf`.
+pub fn library_folders(root: &Path) -> Vec<PathBuf> {
+    let mut folders = vec![root.to_path_buf()];
+    let path = root.join("steamapps").join("libraryfolders.vdf");
+    let document = match fs::read_to_string(&path).map(|text| vdf::parse(&text)) {
+        Ok(Ok(document)) => document,
+        Ok(Err(e)) => {
+            log::warn!("Ignoring invalid {:?}: {}", path, e);
+            return folders;
+        }
+        Err(_) => return folders,
+    };
+    let entries = document.get("libraryfolders").map(Vdf::entries).unwrap_or_default();
+    for (key, value) in entries {
+        if key.parse::<u32>().is_err() {
+            continue;
+        }
+        let folder = value.as_str().or_else(|| value.get("path").and_then(Vdf::as_str));
+        if let Some(folder) = folder.map(PathBuf::from) {
+            if !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+    }
+    folders
+}
+
+/// The name of app `app_id` if it is installed in `library`.
+pub fn manifest_name(library: &Path, app_id: u32) -> Option<String> {
+    let path = library.join("steamapps").join(format!("appmanifest_{}.acf", app_id));
+    let text = fs::read_to_string(&path).ok()?;
+    let document = vdf::parse(&text)
+        .map_err(|e| log::warn!("Ignoring invalid {:?}: {}", path, e))
+        .ok()?;
+    let name = document.get("AppState")?.get("name")?.as_str()?.trim();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::{GameExecutable, ThirdPartySku};
+    use crate::game_scanner::test_support::temp_dir;
+
+    fn process(cmd: &[&str]) -> ProcessInfo {
+        ProcessInfo {
+            name: "reaper".to_string(),
+            cmd: cmd.iter().map(|arg| arg.to_string()).collect(),
+            ..ProcessInfo::default()
+        }
+    }
+
+    /// A Steam root whose library folders point at a second library holding Portal 2.
+    fn steam_root(dir: &Path, library_folders: &str) -> (PathBuf, PathBuf) {
+        let root = dir.join("Steam");
+        let library = dir.join("SteamLibrary");
+        fs::create_dir_all(root.join("steamapps")).unwrap();
+        fs::create_dir_all(library.join("steamapps")).unwrap();
+        let library_folders = library_folders.replace("/mnt/games/SteamLibrary", library.to_str().unwrap());
+        fs::write(root.join("steamapps/libraryfolders.vdf"), library_folders).unwrap();
+        fs::write(
+            library.join("steamapps/appmanifest_620.acf"),
+            include_str!("fixtures/steam/appmanifest_620.acf"),
+        )
+        .unwrap();
+        (root, library)
+    }
+
+    #[test]
+    fn reads_app_ids_from_launch_command_lines() {
+        let reaper = [
+            "/home/user/.local/share/Steam/ubuntu12_32/reaper",
+            "SteamLaunch",
+            "AppId=620",
+            "--",
+            "/home/user/.local/share/Steam/steamapps/common/Portal 2/portal2.sh",
+        ];
+        assert_eq!(app_id(&process(&reaper)), Some(620));
+        assert_eq!(app_id(&process(&["reaper", "SteamLaunch", "appid=1145360"])), Some(1145360));
+        assert_eq!(app_id(&process(&["reaper", "AppId=620"])), None);
+        assert_eq!(app_id(&process(&["reaper", "SteamLaunch", "AppId=abc"])), None);
+    }
+
+    #[test]
+    fn finds_library_folders_in_both_layouts() {
+        for (name, fixture) in [
+            ("current", include_str!("fixtures/steam/libraryfolders.vdf")),
+            ("legacy", include_str!("fixtures/steam/libraryfolders_legacy.vdf")),
+        ] {
+            let dir = temp_dir(&format!("steam-{}", name));
+            let (root, library) = steam_root(&dir, fixture);
+            let folders = library_folders(&root);
+            assert!(folders.contains(&root) && folders.contains(&library), "{}: {:?}", name, folders);
+            assert_eq!(manifest_name(&library, 620).as_deref(), Some("Portal 2"));
+            assert_eq!(manifest_name(&library, 621), None);
+        }
+    }
+
+    #[test]
+    fn resolves_launched_apps_from_the_watch_list_or_manifests() {
+        let dir = temp_dir("steam-resolve");
+        let (root, _) = steam_root(&dir, include_str!("fixtures/steam/libraryfolders.vdf"));
+        let hades = DetectableGame {
+            id: "1234".to_string(),
+            name: "Hades".to_string(),
+            executables: Some(vec![GameExecutable {
+                os: "win32".to_string(),
+                name: "hades.exe".to_string(),
+                ..GameExecutable::default()
+            }]),
+            third_party_skus: vec![ThirdPartySku {
+                distributor: "steam".to_string(),
+                id: Some("1145360".to_string()),
+                sku: None,
+            }],
+            ..DetectableGame::default()
+        };
+        let watch_list = WatchList::new(vec![hades]);
+        let snapshot = vec![
+            process(&["reaper", "SteamLaunch", "AppId=620"]),
+            process(&["reaper", "SteamLaunch", "AppId=1145360"]),
+            process(&["reaper", "SteamLaunch", "AppId=999"]),
+        ];
+
+        let mut library = SteamLibrary::new(vec![root]);
+        let games = library.resolve(&snapshot, &watch_list);
+        assert_eq!(games.get(620).map(|game| (game.id.as_str(), game.name.as_str())), Some(("steam:620", "Portal 2")));
+        assert_eq!(games.get(1145360).map(|game| game.id.as_str()), Some("1234"));
+        assert!(games.get(999).is_none());
+    }
+
+    #[test]
+    fn missing_apps_are_looked_up_again_once_a_library_changes() {
+        let dir = temp_dir("steam-missing");
+        let (root, library) = steam_root(&dir, include_str!("fixtures/steam/libraryfolders.vdf"));
+        let mut steam = SteamLibrary::new(vec![root]);
+        assert_eq!(steam.app_name(999), None);
+        assert!(steam.missing.contains(&999));
+
+        // Let the new manifest land in a later timestamp tick than the stamps.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let manifest = include_str!("fixtures/steam/appmanifest_620.acf").replace("Portal 2", "Portal 3");
+        fs::write(library.join("steamapps/appmanifest_999.acf"), manifest).unwrap();
+        assert_eq!(steam.app_name(999).as_deref(), Some("Portal 3"));
+    }
+}
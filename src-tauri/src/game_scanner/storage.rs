@@ -0,0 +1,65 @@
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Writes `value` to `path` as JSON, creating its directory if needed. The
+/// data goes to a temporary file that is then renamed over `path`, so a crash
+/// mid-write leaves either the old file or the new one, never part of one.
+pub fn write_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = with_suffix(path, ".tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)
+}
+
+/// Moves the invalid file at `path`, which failed to load with `error`, to
+/// `<path>.bad`, so that the next save doesn't overwrite what it held.
+pub fn set_aside(path: &Path, error: impl Display) {
+    let bad = with_suffix(path, ".bad");
+    match fs::rename(path, &bad) {
+        Ok(()) => log::warn!("Moved invalid {:?} to {:?}: {}", path, bad, error),
+        Err(e) => log::warn!("Failed to move invalid {:?} aside: {}", path, e),
+    }
+}
+
+/// `path` with `suffix` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_scanner::test_support::temp_dir;
+
+    #[test]
+    fn writes_replace_the_file_without_leaving_a_temporary_one() {
+        let dir = temp_dir("storage");
+        let path = dir.join("nested").join("store.json");
+        write_json_atomic(&path, &vec!["a", "b"]).unwrap();
+        write_json_atomic(&path, &["c"]).unwrap();
+
+        let loaded: Vec<String> = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(loaded, vec!["c"]);
+        assert!(!path.with_file_name("store.json.tmp").exists());
+    }
+
+    #[test]
+    fn invalid_files_are_set_aside() {
+        let dir = temp_dir("storage-bad");
+        let path = dir.join("store.json");
+        fs::write(&path, b"[{\"id\":").unwrap();
+        set_aside(&path, "invalid");
+
+        assert!(!path.exists());
+        assert_eq!(fs::read(path.with_file_name("store.json.bad")).unwrap(), b"[{\"id\":");
+    }
+}
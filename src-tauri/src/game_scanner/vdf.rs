@@ -0,0 +1,207 @@
+//! A reader for Valve's KeyValues text format, as used by Steam's
+//! `libraryfolders.vdf` and `appmanifest_*.acf` files.
+//!
+//! A document is a sequence of `"key" "value"` and `"key" { ... }` pairs.
+//! Keys are compared case-insensitively, `//` starts a comment, and platform
+//! conditionals such as `[$WIN32]` are skipped.
+
+use std::fmt;
+
+/// A value in a KeyValues document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Vdf {
+    Value(String),
+    Object(Vec<(String, Vdf)>),
+}
+
+impl Vdf {
+    /// The first child named `key`, if this is an object.
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        self.entries()
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Vdf::Value(value) => Some(value),
+            Vdf::Object(_) => None,
+        }
+    }
+
+    /// The children of an object; empty for plain values.
+    pub fn entries(&self) -> &[(String, Vdf)] {
+        match self {
+            Vdf::Object(entries) => entries,
+            Vdf::Value(_) => &[],
+        }
+    }
+}
+
+/// Why a document couldn't be read.
+#[derive(Debug, PartialEq)]
+pub struct VdfError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for VdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for VdfError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Open,
+    Close,
+}
+
+struct Tokens<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Tokens<'_> {
+    fn error(&self, message: &'static str) -> VdfError {
+        VdfError { line: self.line, message }
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, VdfError> {
+        loop {
+            let c = match self.chars.next() {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+            match c {
+                '\n' => self.line += 1,
+                c if c.is_whitespace() => {}
+                '/' if self.chars.peek() == Some(&'/') => {
+                    while self.chars.peek().map_or(false, |&c| c != '\n') {
+                        self.chars.next();
+                    }
+                }
+                '{' => return Ok(Some(Token::Open)),
+                '}' => return Ok(Some(Token::Close)),
+                '"' => return self.quoted().map(|text| Some(Token::Text(text))),
+                '[' => {
+                    // A conditional such as `[$WIN32]` on the previous pair.
+                    while self.chars.next().map_or(false, |c| c != ']') {}
+                }
+                c => {
+                    let mut text = c.to_string();
+                    while let Some(&c) = self.chars.peek() {
+                        if c.is_whitespace() || matches!(c, '"' | '{' | '}') {
+                            break;
+                        }
+                        text.push(c);
+                        self.chars.next();
+                    }
+                    return Ok(Some(Token::Text(text)));
+                }
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, VdfError> {
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some(c) => text.push(c),
+                    None => break,
+                },
+                Some(c) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    text.push(c);
+                }
+                None => break,
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+}
+
+/// Parses a document into an object holding its top-level pairs.
+pub fn parse(text: &str) -> Result<Vdf, VdfError> {
+    let mut tokens = Tokens {
+        chars: text.trim_start_matches('\u{feff}').chars().peekable(),
+        line: 1,
+    };
+    let entries = parse_entries(&mut tokens, false)?;
+    Ok(Vdf::Object(entries))
+}
+
+fn parse_entries(tokens: &mut Tokens, nested: bool) -> Result<Vec<(String, Vdf)>, VdfError> {
+    let mut entries = Vec::new();
+    loop {
+        let key = match tokens.next()? {
+            Some(Token::Text(key)) => key,
+            Some(Token::Close) if nested => return Ok(entries),
+            None if !nested => return Ok(entries),
+            None => return Err(tokens.error("missing '}'")),
+            Some(_) => return Err(tokens.error("expected a key")),
+        };
+        let value = match tokens.next()? {
+            Some(Token::Text(value)) => Vdf::Value(value),
+            Some(Token::Open) => Vdf::Object(parse_entries(tokens, true)?),
+            _ => return Err(tokens.error("expected a value")),
+        };
+        entries.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_library_folders() {
+        let vdf = parse(include_str!("fixtures/steam/libraryfolders.vdf")).unwrap();
+        let folders = vdf.get("libraryfolders").unwrap();
+
+        assert_eq!(folders.entries().len(), 2);
+        let second = folders.get("1").unwrap();
+        assert_eq!(second.get("path").and_then(Vdf::as_str), Some("/mnt/games/SteamLibrary"));
+        assert_eq!(second.get("label").and_then(Vdf::as_str), Some("Games \"SSD\""));
+        let apps: Vec<&str> = second.get("apps").unwrap().entries().iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(apps, vec!["620", "1145360"]);
+    }
+
+    #[test]
+    fn parses_app_manifests() {
+        let vdf = parse(include_str!("fixtures/steam/appmanifest_620.acf")).unwrap();
+        let app = vdf.get("AppState").unwrap();
+
+        assert_eq!(app.get("appid").and_then(Vdf::as_str), Some("620"));
+        assert_eq!(app.get("NAME").and_then(Vdf::as_str), Some("Portal 2"));
+        assert_eq!(app.get("installdir").and_then(Vdf::as_str), Some("Portal 2"));
+        assert!(app.get("UserConfig").unwrap().get("language").is_some());
+    }
+
+    #[test]
+    fn handles_comments_conditionals_and_bare_tokens() {
+        let vdf = parse("// header\n\"root\"\n{\n  key value [$WIN32] // trailing\n  \"empty\" \"\"\n}\n").unwrap();
+        let root = vdf.get("root").unwrap();
+
+        assert_eq!(root.get("key").and_then(Vdf::as_str), Some("value"));
+        assert_eq!(root.get("empty").and_then(Vdf::as_str), Some(""));
+    }
+
+    #[test]
+    fn reports_malformed_documents() {
+        assert_eq!(parse("\"a\"\n{\n\"b\" \"c\"\n").unwrap_err().message, "missing '}'");
+        assert_eq!(parse("\"a\" \"b").unwrap_err().message, "unterminated string");
+        assert_eq!(parse("\"a\"\n}").unwrap_err(), VdfError { line: 2, message: "expected a value" });
+        assert!(parse("}").is_err());
+    }
+}
@@ -11,6 +11,8 @@ mod game_scanner;
 use tauri::{utils::config::AppUrl, WindowUrl};
 
 fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("cinny=info")).init();
+
     let port = 44548;
 
     let mut context = tauri::generate_context!();
@@ -36,24 +38,58 @@ fn main() {
         builder = builder.plugin(tauri_plugin_localhost::Builder::new(port).build());
     }
 
-    let mut scanner_state = game_scanner::ScannerState {
-        watch_list: std::sync::Mutex::new(Vec::new()),
-        current_game: std::sync::Mutex::new(None),
-        is_enabled: std::sync::Mutex::new(false),
-        notify: std::sync::Arc::new(tokio::sync::Notify::new()),
-    };
-    let scanner_state_arc = std::sync::Arc::new(scanner_state);
+    let scanner_settings = game_scanner::load_settings(context.config());
+    let (scanner_state, scanner_inbox) = game_scanner::ScannerState::new(scanner_settings);
 
     builder
-        .manage(scanner_state_arc.clone())
+        .manage(scanner_state.clone())
         .plugin(tauri_plugin_window_state::Builder::default().build())
         .setup(move |app| {
-            game_scanner::start(app.handle().clone(), scanner_state_arc);
+            game_scanner::start(app.handle(), scanner_state, scanner_inbox);
             Ok(())
         })
+        .on_window_event(|event| {
+            if let tauri::WindowEvent::Focused(focused) = event.event() {
+                game_scanner::set_app_focused(event.window(), *focused);
+            }
+        })
         .invoke_handler(tauri::generate_handler![
-            game_scanner::set_scanner_enabled
+            game_scanner::set_scanner_enabled,
+            game_scanner::get_games_source,
+            game_scanner::set_games_source,
+            game_scanner::set_compatibility_layer,
+            game_scanner::get_running_games,
+            game_scanner::get_current_activity,
+            game_scanner::set_history_enabled,
+            game_scanner::set_scanner_hysteresis,
+            game_scanner::get_playtime_totals,
+            game_scanner::get_playtime_by_day,
+            game_scanner::get_recent_sessions,
+            game_scanner::set_primary_policy,
+            game_scanner::list_custom_games,
+            game_scanner::add_custom_game,
+            game_scanner::remove_custom_game,
+            game_scanner::set_scanner_interval,
+            game_scanner::get_scanner_metrics,
+            game_scanner::set_process_events,
+            game_scanner::rescan_games,
+            game_scanner::set_manual_activity,
+            game_scanner::clear_manual_activity,
+            game_scanner::get_ignore_list,
+            game_scanner::set_game_ignored,
+            game_scanner::set_category_ignored,
+            game_scanner::refresh_games_list,
+            game_scanner::set_games_list_refresh_interval,
+            game_scanner::get_scanner_status,
+            game_scanner::restart_game_scanner,
+            game_scanner::set_launcher_policy,
+            game_scanner::set_launcher_libraries
         ])
-        .run(context)
+        .build(context)
         .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                game_scanner::stop(app);
+            }
+        })
 }